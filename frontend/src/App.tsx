@@ -32,6 +32,52 @@ interface ManifestPayload {
   active_seq: number;
   segments: ManifestSegment[];
   checkpoints: ManifestCheckpoint[];
+  preview?: ManifestSegment;
+  status?: 'live' | 'complete';
+  ended_at?: string;
+  metadata?: SessionMetadata;
+  stats?: SessionStats;
+}
+
+interface SessionStats {
+  lines: number;
+  bytes_uncompressed: number;
+  bytes_compressed: number;
+  duration_secs: number;
+  event_types: Record<string, number>;
+  invalid_json: number;
+  inferred_timestamps?: number;
+  redactions?: Record<string, number>;
+  dropped?: number;
+  truncated?: number;
+}
+
+interface SessionMetadata {
+  cwd?: string;
+  model?: string;
+  cli_version?: string;
+  originator?: string;
+  instructions?: string;
+  git?: { commit?: string; branch?: string; repository_url?: string };
+  hostname?: string;
+  os_user?: string;
+}
+
+interface SessionSummary {
+  sid: string;
+  created_at: string;
+  updated_at: string;
+  lines: number;
+  bytes_uncompressed: number;
+  segments: number;
+  checkpoints: number;
+  status?: 'live' | 'complete';
+  ended_at?: string;
+  host?: string;
+  model?: string;
+  repo?: string;
+  branch?: string;
+  cwd?: string;
 }
 
 interface SessionPayload {
@@ -39,6 +85,17 @@ interface SessionPayload {
   manifest: ManifestPayload;
 }
 
+interface HeartbeatPayload {
+  sid: string;
+  host: string;
+  pid: number;
+  updated_at: string;
+  active_seq: number;
+  buffered_lines: number;
+  spool_depth: number;
+  last_upload_error?: string;
+}
+
 interface TerminalLine {
   ts: number;
   type: string;
@@ -49,8 +106,14 @@ interface TerminalLine {
   [key: string]: unknown;
 }
 
-async function fetchSessions(): Promise<SessionPayload[]> {
-  const response = await fetch('/api/sessions');
+const SESSION_PAGE_SIZE = 200;
+
+async function fetchSessions(prefix: string): Promise<SessionSummary[]> {
+  const params = new URLSearchParams({ sort: 'updated_at', order: 'desc', limit: String(SESSION_PAGE_SIZE) });
+  if (prefix) {
+    params.set('prefix', prefix);
+  }
+  const response = await fetch(`/api/sessions?${params.toString()}`);
   if (!response.ok) {
     const message = await response.text();
     throw new Error(message || `Failed to load sessions (${response.status})`);
@@ -59,29 +122,109 @@ async function fetchSessions(): Promise<SessionPayload[]> {
   return data.sessions ?? [];
 }
 
-async function fetchReplayLines(sid: string, seq: number, lineIdx: number, maxLines: number): Promise<TerminalLine[]> {
-  const params = new URLSearchParams({
+async function fetchManifest(sid: string): Promise<ManifestPayload> {
+  const response = await fetch(`/api/sessions/${encodeURIComponent(sid)}`);
+  if (!response.ok) {
+    const message = await response.text();
+    throw new Error(message || `Failed to load manifest (${response.status})`);
+  }
+  return response.json();
+}
+
+interface TokenUsage {
+  input_tokens: number;
+  cached_input_tokens: number;
+  output_tokens: number;
+  reasoning_output_tokens: number;
+  total_tokens: number;
+}
+
+interface UsageReport {
+  sid: string;
+  tokens: TokenUsage;
+  cost_usd?: number;
+  checkpoints: Array<{ id: string; tokens: TokenUsage; cost_usd?: number }>;
+}
+
+async function fetchUsage(sid: string): Promise<UsageReport | null> {
+  const response = await fetch(`/api/sessions/${encodeURIComponent(sid)}/usage`);
+  if (!response.ok) {
+    return null;
+  }
+  return response.json();
+}
+
+function formatCost(cost: number | undefined): string | undefined {
+  return cost === undefined ? undefined : `$${cost.toFixed(2)}`;
+}
+
+interface ReplayPage {
+  lines: TerminalLine[];
+  prev?: string;
+  next?: string;
+}
+
+async function fetchReplayPage(sid: string, params: Record<string, string>): Promise<ReplayPage> {
+  const query = new URLSearchParams(params);
+  const response = await fetch(`/api/sessions/${encodeURIComponent(sid)}/replay?${query.toString()}`);
+  if (!response.ok) {
+    const message = await response.text();
+    throw new Error(message || `Failed to load replay (${response.status})`);
+  }
+  const data = await response.json();
+  return { lines: data.lines ?? [], prev: data.prev, next: data.next };
+}
+
+async function fetchReplayLines(sid: string, seq: number, lineIdx: number, maxLines: number): Promise<ReplayPage> {
+  return fetchReplayPage(sid, {
     seq: String(seq),
     line_idx: String(lineIdx),
     max_lines: String(maxLines),
   });
-  const response = await fetch(`/api/sessions/${encodeURIComponent(sid)}/replay?${params.toString()}`);
+}
+
+async function fetchHeartbeat(sid: string): Promise<HeartbeatPayload | null> {
+  const response = await fetch(`/api/sessions/${encodeURIComponent(sid)}/live`);
   if (!response.ok) {
-    const message = await response.text();
-    throw new Error(message || `Failed to load replay (${response.status})`);
+    return null;
   }
-  const data = await response.json();
-  return data.lines ?? [];
+  return response.json();
+}
+
+function formatBytes(bytes: number): string {
+  const units = ['B', 'KB', 'MB', 'GB'];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit += 1;
+  }
+  return `${unit === 0 ? value : value.toFixed(1)} ${units[unit]}`;
+}
+
+function formatDuration(secs: number): string {
+  const hours = Math.floor(secs / 3600);
+  const minutes = Math.floor((secs % 3600) / 60);
+  if (hours > 0) return `${hours}h ${minutes}m`;
+  if (minutes > 0) return `${minutes}m ${secs % 60}s`;
+  return `${secs}s`;
 }
 
 export default function App() {
-  const [sessions, setSessions] = useState<SessionPayload[]>([]);
+  const [sessions, setSessions] = useState<SessionSummary[]>([]);
+  const [currentManifest, setCurrentManifest] = useState<ManifestPayload | null>(null);
+  const [usage, setUsage] = useState<UsageReport | null>(null);
+  const [sessionFilter, setSessionFilter] = useState<string>('');
   const [selectedSession, setSelectedSession] = useState<string>('');
   const [selectedCheckpoint, setSelectedCheckpoint] = useState<string>('latest');
   const [terminalLines, setTerminalLines] = useState<TerminalLine[]>([]);
   const [isReplaying, setIsReplaying] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [loading, setLoading] = useState<boolean>(true);
+  const [heartbeat, setHeartbeat] = useState<HeartbeatPayload | null>(null);
+  const [isFollowing, setIsFollowing] = useState(false);
+  const [earlierCursor, setEarlierCursor] = useState<string | null>(null);
+  const followSourceRef = useRef<EventSource | null>(null);
   const replayTimeoutRef = useRef<NodeJS.Timeout | null>(null);
 
   useEffect(() => {
@@ -90,7 +233,7 @@ export default function App() {
     const load = async () => {
       try {
         setLoading(true);
-        const result = await fetchSessions();
+        const result = await fetchSessions(sessionFilter.trim());
         if (mounted) {
           setSessions(result);
           setError(null);
@@ -113,7 +256,7 @@ export default function App() {
       mounted = false;
       clearInterval(timer);
     };
-  }, []);
+  }, [sessionFilter]);
 
   useEffect(() => {
     if (replayTimeoutRef.current) {
@@ -135,15 +278,143 @@ export default function App() {
     });
   }, [sessions]);
 
+  const stopFollowing = () => {
+    followSourceRef.current?.close();
+    followSourceRef.current = null;
+    setIsFollowing(false);
+  };
+
   useEffect(() => {
     setSelectedCheckpoint('latest');
     setTerminalLines([]);
+    setEarlierCursor(null);
+    stopFollowing();
+  }, [selectedSession]);
+
+  useEffect(() => {
+    setHeartbeat(null);
+    if (!selectedSession) return;
+    let mounted = true;
+    const load = async () => {
+      const result = await fetchHeartbeat(selectedSession).catch(() => null);
+      if (mounted) {
+        setHeartbeat(result);
+      }
+    };
+    load();
+    const timer = setInterval(load, 5_000);
+    return () => {
+      mounted = false;
+      clearInterval(timer);
+    };
   }, [selectedSession]);
 
-  const currentSession = useMemo(() => sessions.find((s) => s.sid === selectedSession) ?? null, [sessions, selectedSession]);
+  // The list only carries index summaries; reload the selected manifest whenever the list
+  // shows that session changed.
+  const selectedSummary = sessions.find((s) => s.sid === selectedSession);
+  useEffect(() => {
+    if (!selectedSession) {
+      setCurrentManifest(null);
+      setUsage(null);
+      return;
+    }
+    let mounted = true;
+    fetchUsage(selectedSession)
+      .then((report) => {
+        if (mounted) {
+          setUsage(report);
+        }
+      })
+      .catch(() => undefined);
+    fetchManifest(selectedSession)
+      .then((manifest) => {
+        if (mounted) {
+          setCurrentManifest(manifest);
+        }
+      })
+      .catch((err) => {
+        if (mounted) {
+          setError(err instanceof Error ? err.message : String(err));
+        }
+      });
+    return () => {
+      mounted = false;
+    };
+  }, [selectedSession, selectedSummary?.updated_at]);
+
+  const currentSession = useMemo<SessionPayload | null>(
+    () => (currentManifest && currentManifest.sid === selectedSession ? { sid: selectedSession, manifest: currentManifest } : null),
+    [currentManifest, selectedSession],
+  );
 
   const sessionIds = useMemo(() => sessions.map((s) => s.sid), [sessions]);
 
+  const sessionDetails = useMemo(
+    () =>
+      Object.fromEntries(
+        sessions.map((s) => [s.sid, [s.model, s.host, s.repo?.replace(/\.git$/, '').split('/').pop()].filter(Boolean).join(' · ')]),
+      ),
+    [sessions],
+  );
+
+  const metadataRows = useMemo(() => {
+    const meta = currentSession?.manifest.metadata;
+    if (!meta) return [];
+    const git = meta.git;
+    const rows: Array<[string, string | undefined]> = [
+      ['cwd', meta.cwd],
+      ['model', meta.model],
+      ['cli', [meta.originator, meta.cli_version].filter(Boolean).join(' ') || undefined],
+      ['repo', git?.repository_url],
+      ['branch', git?.branch],
+      ['commit', git?.commit?.slice(0, 12)],
+      ['host', [meta.os_user, meta.hostname].filter(Boolean).join('@') || undefined],
+    ];
+    return rows.filter((row): row is [string, string] => Boolean(row[1])).map(([label, value]) => ({ label, value }));
+  }, [currentSession]);
+
+  const statsRows = useMemo(() => {
+    const stats = currentSession?.manifest.stats;
+    if (!stats || stats.lines === 0) return [];
+    const rows = [
+      { label: 'lines', value: stats.lines.toLocaleString() },
+      { label: 'size', value: `${formatBytes(stats.bytes_uncompressed)} (${formatBytes(stats.bytes_compressed)} stored)` },
+      { label: 'duration', value: formatDuration(stats.duration_secs) },
+    ];
+    if (usage && usage.tokens.total_tokens > 0) {
+      const { input_tokens, cached_input_tokens, output_tokens } = usage.tokens;
+      rows.push({
+        label: 'tokens',
+        value: `${input_tokens.toLocaleString()} in (${cached_input_tokens.toLocaleString()} cached) / ${output_tokens.toLocaleString()} out`,
+      });
+      const cost = formatCost(usage.cost_usd);
+      if (cost) {
+        rows.push({ label: 'est. cost', value: cost });
+      }
+    }
+    if (stats.invalid_json > 0) {
+      rows.push({ label: 'invalid json', value: stats.invalid_json.toLocaleString() });
+    }
+    if (stats.inferred_timestamps) {
+      rows.push({ label: 'no timestamp', value: stats.inferred_timestamps.toLocaleString() });
+    }
+    if (stats.dropped) {
+      rows.push({ label: 'dropped', value: stats.dropped.toLocaleString() });
+    }
+    if (stats.truncated) {
+      rows.push({ label: 'truncated', value: stats.truncated.toLocaleString() });
+    }
+    const redacted = Object.values(stats.redactions ?? {}).reduce((sum, count) => sum + count, 0);
+    if (redacted > 0) {
+      rows.push({ label: 'redacted', value: redacted.toLocaleString() });
+    }
+    const types = Object.entries(stats.event_types).sort((a, b) => b[1] - a[1]);
+    for (const [type, count] of types.slice(0, 8)) {
+      rows.push({ label: type, value: count.toLocaleString() });
+    }
+    return rows;
+  }, [currentSession, usage]);
+
   const checkpointOptions = useMemo(() => {
     const cps = currentSession?.manifest.checkpoints ?? [];
     const options = cps.map((cp) => ({ value: cp.id, label: cp.label || cp.id }));
@@ -163,7 +434,12 @@ export default function App() {
         const last = cps[cps.length - 1];
         return { seq: last.seq, lineIdx: last.line_idx };
       }
-      const lastSeg = currentSession.manifest.segments[currentSession.manifest.segments.length - 1];
+      const lastSeg =
+        currentSession.manifest.preview ??
+        currentSession.manifest.segments[currentSession.manifest.segments.length - 1];
+      if (!lastSeg) {
+        return { seq: 1, lineIdx: 0 };
+      }
       return { seq: lastSeg.seq, lineIdx: Math.max(0, lastSeg.lines - 1) };
     }
     const cp = currentSession.manifest.checkpoints.find((c) => c.id === selectedCheckpoint);
@@ -173,22 +449,24 @@ export default function App() {
     return { seq: 1, lineIdx: 0 };
   };
 
-  const loadLines = async () => {
-    if (!currentSession) return [] as TerminalLine[];
+  const loadLines = async (): Promise<ReplayPage> => {
+    if (!currentSession) return { lines: [] };
     const { seq, lineIdx } = getTarget();
     return fetchReplayLines(currentSession.sid, seq, lineIdx, 5000);
   };
 
   const handleOpen = async () => {
     if (!currentSession) return;
+    stopFollowing();
     if (replayTimeoutRef.current) {
       clearTimeout(replayTimeoutRef.current);
       replayTimeoutRef.current = null;
     }
     setIsReplaying(false);
     try {
-      const lines = await loadLines();
-      setTerminalLines(lines);
+      const page = await loadLines();
+      setTerminalLines(page.lines);
+      setEarlierCursor(page.prev ?? null);
       setError(null);
     } catch (err) {
       setError(err instanceof Error ? err.message : String(err));
@@ -197,13 +475,15 @@ export default function App() {
 
   const handleReplay = async (speed: number) => {
     if (!currentSession) return;
+    stopFollowing();
     if (replayTimeoutRef.current) {
       clearTimeout(replayTimeoutRef.current);
       replayTimeoutRef.current = null;
     }
     try {
-      const lines = await loadLines();
+      const { lines } = await loadLines();
       setTerminalLines([]);
+      setEarlierCursor(null);
       setIsReplaying(true);
 
       const interval = speed === 1 ? 200 : 50;
@@ -234,11 +514,64 @@ export default function App() {
     }
   };
 
+  const handleLoadEarlier = async () => {
+    if (!currentSession || !earlierCursor) return;
+    try {
+      const page = await fetchReplayPage(currentSession.sid, {
+        cursor: earlierCursor,
+        direction: 'backward',
+        max_lines: '1000',
+      });
+      setTerminalLines((prev) => [...page.lines, ...prev]);
+      setEarlierCursor(page.prev ?? null);
+      setError(null);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  const handleFollow = () => {
+    if (isFollowing) {
+      stopFollowing();
+      return;
+    }
+    if (!currentSession) return;
+    if (replayTimeoutRef.current) {
+      clearTimeout(replayTimeoutRef.current);
+      replayTimeoutRef.current = null;
+    }
+    setIsReplaying(false);
+    setTerminalLines([]);
+    setEarlierCursor(null);
+    // EventSource reconnects on its own and resumes via Last-Event-ID.
+    const source = new EventSource(`/api/sessions/${encodeURIComponent(currentSession.sid)}/tail`);
+    source.onmessage = (event) => {
+      const data = JSON.parse(event.data) as { line: TerminalLine };
+      setTerminalLines((prev) => {
+        const next = [...prev, data.line];
+        if (next.length > 5000) {
+          return next.slice(-5000);
+        }
+        return next;
+      });
+    };
+    source.onerror = () => {
+      if (source.readyState === EventSource.CLOSED) {
+        setError('Live tail closed by the server');
+        stopFollowing();
+      }
+    };
+    followSourceRef.current = source;
+    setIsFollowing(true);
+    setError(null);
+  };
+
   useEffect(() => {
     return () => {
       if (replayTimeoutRef.current) {
         clearTimeout(replayTimeoutRef.current);
       }
+      followSourceRef.current?.close();
     };
   }, []);
 
@@ -246,6 +579,16 @@ export default function App() {
     ? new Date(currentSession.manifest.updated_at).toLocaleString()
     : 'n/a';
 
+  const sessionStatus = currentSession
+    ? currentSession.manifest.status === 'complete'
+      ? `complete${currentSession.manifest.ended_at ? ` (ended ${new Date(currentSession.manifest.ended_at).toLocaleString()})` : ''}`
+      : heartbeat
+        ? `live, ${Math.max(0, Math.round((Date.now() - new Date(heartbeat.updated_at).getTime()) / 1000))} s ago on ${heartbeat.host}` +
+          ` (seq ${heartbeat.active_seq}, ${heartbeat.buffered_lines} buffered, ${heartbeat.spool_depth} queued)` +
+          (heartbeat.last_upload_error ? ` • upload error: ${heartbeat.last_upload_error}` : '')
+        : 'live'
+    : 'n/a';
+
   return (
     <div className="flex h-screen flex-col bg-zinc-950 text-zinc-100">
       <TopBar
@@ -258,7 +601,9 @@ export default function App() {
         onOpen={handleOpen}
         onReplay1x={() => handleReplay(1)}
         onReplay4x={() => handleReplay(4)}
+        onFollow={handleFollow}
         isReplaying={isReplaying}
+        isFollowing={isFollowing}
       />
 
       {error && (
@@ -273,12 +618,18 @@ export default function App() {
       <div className="grid flex-1 grid-cols-[280px_1fr] gap-4 p-4">
         <Sidebar
           sessions={sessionIds}
+          sessionDetails={sessionDetails}
+          metadata={metadataRows}
+          stats={statsRows}
           selectedSession={selectedSession}
           onSessionSelect={(sid) => setSelectedSession(sid)}
+          sessionFilter={sessionFilter}
+          onSessionFilterChange={setSessionFilter}
           checkpoints={checkpoints.map((cp) => ({
             ...cp,
             label: cp.label || cp.id,
             git: cp.git || '',
+            cost: formatCost(usage?.checkpoints.find((entry) => entry.id === cp.id)?.cost_usd),
           }))}
           selectedCheckpoint={selectedCheckpoint}
           onCheckpointSelect={(id) => setSelectedCheckpoint(id)}
@@ -287,13 +638,17 @@ export default function App() {
             gzip_bytes: segment.bytes_gzip ?? 0,
           }))}
         />
-        <TerminalPanel lines={terminalLines} />
+        <TerminalPanel
+          lines={terminalLines}
+          onLoadEarlier={earlierCursor && !isReplaying && !isFollowing ? handleLoadEarlier : undefined}
+        />
       </div>
 
       <StatusBar
         segmentCount={segments.length}
         checkpointCount={checkpoints.length}
         lastUpdate={lastUpdate}
+        status={sessionStatus}
       />
     </div>
   );
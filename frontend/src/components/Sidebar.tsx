@@ -14,12 +14,18 @@ interface Checkpoint {
   line_idx: number;
   git: string;
   ts: number;
+  cost?: string;
 }
 
 interface SidebarProps {
   sessions: string[];
+  sessionDetails: Record<string, string>;
+  metadata: Array<{ label: string; value: string }>;
+  stats: Array<{ label: string; value: string }>;
   selectedSession: string;
   onSessionSelect: (sid: string) => void;
+  sessionFilter: string;
+  onSessionFilterChange: (prefix: string) => void;
   checkpoints: Checkpoint[];
   selectedCheckpoint: string;
   onCheckpointSelect: (id: string) => void;
@@ -28,8 +34,13 @@ interface SidebarProps {
 
 export function Sidebar({
   sessions,
+  sessionDetails,
+  metadata,
+  stats,
   selectedSession,
   onSessionSelect,
+  sessionFilter,
+  onSessionFilterChange,
   checkpoints,
   selectedCheckpoint,
   onCheckpointSelect,
@@ -40,6 +51,12 @@ export function Sidebar({
       {/* Sessions List */}
       <div>
         <h3 className="mb-2 text-sm text-zinc-400">Sessions</h3>
+        <input
+          value={sessionFilter}
+          onChange={(e) => onSessionFilterChange(e.target.value)}
+          placeholder="Filter by sid prefix"
+          className="mb-2 w-full rounded border border-zinc-700 bg-zinc-900 px-2 py-1 text-xs text-zinc-200"
+        />
         <div className="space-y-1">
           {sessions.map((sid) => (
             <div
@@ -51,12 +68,43 @@ export function Sidebar({
                   : 'text-zinc-400 hover:bg-zinc-800'
               }`}
             >
-              {sid}
+              <div>{sid}</div>
+              {sessionDetails[sid] && <div className="truncate text-[10px] text-zinc-600">{sessionDetails[sid]}</div>}
             </div>
           ))}
         </div>
       </div>
 
+      {/* Session Stats */}
+      {stats.length > 0 && (
+        <div>
+          <h3 className="mb-2 text-sm text-zinc-400">Stats</h3>
+          <dl className="space-y-1 text-xs">
+            {stats.map(({ label, value }) => (
+              <div key={label} className="flex gap-2">
+                <dt className="w-24 shrink-0 truncate text-zinc-600">{label}</dt>
+                <dd className="text-zinc-400">{value}</dd>
+              </div>
+            ))}
+          </dl>
+        </div>
+      )}
+
+      {/* Session Metadata */}
+      {metadata.length > 0 && (
+        <div>
+          <h3 className="mb-2 text-sm text-zinc-400">Metadata</h3>
+          <dl className="space-y-1 text-xs">
+            {metadata.map(({ label, value }) => (
+              <div key={label} className="flex gap-2">
+                <dt className="w-16 shrink-0 text-zinc-600">{label}</dt>
+                <dd className="break-all text-zinc-400">{value}</dd>
+              </div>
+            ))}
+          </dl>
+        </div>
+      )}
+
       {/* Checkpoints Timeline */}
       <div>
         <h3 className="mb-2 text-sm text-zinc-400">Checkpoints</h3>
@@ -77,6 +125,7 @@ export function Sidebar({
                 <div className="font-mono">{cp.label}</div>
                 <div className="text-zinc-600">
                   seq:{cp.seq} line:{cp.line_idx} git:{cp.git}
+                  {cp.cost && ` · ${cp.cost}`}
                 </div>
               </div>
             ))
@@ -2,12 +2,13 @@ interface StatusBarProps {
   segmentCount: number;
   checkpointCount: number;
   lastUpdate: string;
+  status: string;
 }
 
-export function StatusBar({ segmentCount, checkpointCount, lastUpdate }: StatusBarProps) {
+export function StatusBar({ segmentCount, checkpointCount, lastUpdate, status }: StatusBarProps) {
   return (
     <div className="border-t border-zinc-800 bg-zinc-950 px-4 py-2 text-xs text-zinc-600">
-      segments: {segmentCount} • checkpoints: {checkpointCount} • last update: {lastUpdate}
+      status: {status} • segments: {segmentCount} • checkpoints: {checkpointCount} • last update: {lastUpdate}
     </div>
   );
 }
@@ -11,16 +11,20 @@ interface TerminalLine {
 
 interface TerminalPanelProps {
   lines: TerminalLine[];
+  onLoadEarlier?: () => void;
 }
 
-export function TerminalPanel({ lines }: TerminalPanelProps) {
+export function TerminalPanel({ lines, onLoadEarlier }: TerminalPanelProps) {
   const terminalRef = useRef<HTMLDivElement>(null);
+  const lastLineRef = useRef<TerminalLine | undefined>(undefined);
 
   useEffect(() => {
-    // Auto-scroll to bottom when lines change
-    if (terminalRef.current) {
+    // Auto-scroll to bottom when lines are appended, not when earlier lines are prepended
+    const lastLine = lines[lines.length - 1];
+    if (terminalRef.current && lastLine !== lastLineRef.current) {
       terminalRef.current.scrollTop = terminalRef.current.scrollHeight;
     }
+    lastLineRef.current = lastLine;
   }, [lines]);
 
   const getLineClass = (type: string) => {
@@ -61,6 +65,14 @@ export function TerminalPanel({ lines }: TerminalPanelProps) {
       ref={terminalRef}
       className="h-[64vh] overflow-y-auto border border-zinc-800 bg-zinc-950 p-4 font-mono text-sm"
     >
+      {onLoadEarlier && (
+        <button
+          onClick={onLoadEarlier}
+          className="mb-2 rounded border border-zinc-700 px-2 py-0.5 text-xs text-zinc-400 hover:text-zinc-200"
+        >
+          Load earlier
+        </button>
+      )}
       {validLines.length === 0 ? (
         <div className="text-zinc-600">
           Terminal ready. Select a session and click Open or Replay.
@@ -8,7 +8,9 @@ interface TopBarProps {
   onOpen: () => void;
   onReplay1x: () => void;
   onReplay4x: () => void;
+  onFollow: () => void;
   isReplaying: boolean;
+  isFollowing: boolean;
 }
 
 export function TopBar({
@@ -21,7 +23,9 @@ export function TopBar({
   onOpen,
   onReplay1x,
   onReplay4x,
+  onFollow,
   isReplaying,
+  isFollowing,
 }: TopBarProps) {
   return (
     <div className="flex items-center justify-between border-b border-zinc-800 bg-zinc-950 px-4 py-2">
@@ -85,6 +89,13 @@ export function TopBar({
         >
           x4
         </button>
+        <button
+          onClick={onFollow}
+          disabled={isReplaying}
+          className="rounded bg-amber-600 px-3 py-1 text-sm text-white hover:bg-amber-700 disabled:opacity-50"
+        >
+          {isFollowing ? 'Stop' : 'Follow'}
+        </button>
       </div>
 
       <div className="w-20"></div> {/* Spacer for balance */}
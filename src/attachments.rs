@@ -0,0 +1,154 @@
+use crate::tail::SessionEvent;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Object directory under the session prefix: `sessions/<sid>/attachments/<sha256>`.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+/// A string moved out of a line is replaced by this prefix and its SHA-256.
+pub const REFERENCE_PREFIX: &str = "attachment:sha256:";
+/// Embedded `data:...;base64,` payloads are offloaded from this size even when the
+/// general threshold is higher.
+const DATA_URL_MIN_BYTES: usize = 1024;
+
+/// Where an attachment was stored, keyed by its hash in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentEntry {
+    /// Object path relative to the session prefix, with any `.gz`/`.zst`/`.enc` suffix.
+    pub path: String,
+    /// Length of the original string.
+    pub bytes: u64,
+}
+
+/// A string value taken out of an event.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub hash: String,
+    pub body: String,
+}
+
+/// Which strings `--attachment-bytes` moves into attachment objects.
+#[derive(Debug, Clone, Copy)]
+pub struct AttachmentPolicy {
+    pub min_bytes: usize,
+}
+
+impl AttachmentPolicy {
+    /// Replace every large string in a JSON line with a reference and return what was
+    /// taken out. The line is re-encoded only when something moved; other lines, and
+    /// lines that are not JSON, are left alone.
+    pub fn offload(&self, event: &mut SessionEvent) -> Vec<Attachment> {
+        let mut attachments = Vec::new();
+        if let Some(json) = event.json.as_mut() {
+            self.offload_value(json, &mut attachments);
+            if !attachments.is_empty()
+                && let Ok(raw) = serde_json::to_vec(json)
+            {
+                event.raw = raw;
+            }
+        }
+        attachments
+    }
+
+    fn offload_value(&self, value: &mut Value, out: &mut Vec<Attachment>) {
+        match value {
+            Value::String(text) if self.wants(text) => {
+                let body = std::mem::take(text);
+                let hash = content_hash(&body);
+                *text = format!("{REFERENCE_PREFIX}{hash}");
+                out.push(Attachment { hash, body });
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.offload_value(item, out)),
+            Value::Object(map) => map
+                .values_mut()
+                .for_each(|item| self.offload_value(item, out)),
+            _ => {}
+        }
+    }
+
+    fn wants(&self, text: &str) -> bool {
+        if text.len() >= self.min_bytes {
+            return true;
+        }
+        text.len() >= DATA_URL_MIN_BYTES && text.starts_with("data:") && text.contains(";base64,")
+    }
+}
+
+/// Hex SHA-256 of an attachment body, which is also its object name.
+pub fn content_hash(body: &str) -> String {
+    hex::encode(Sha256::digest(body.as_bytes()))
+}
+
+/// The hash named by a reference string, if `text` is one.
+pub fn parse_reference(text: &str) -> Option<&str> {
+    text.strip_prefix(REFERENCE_PREFIX)
+        .filter(|hash| hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Collect the hashes of every reference in `value`.
+pub fn references(value: &Value, out: &mut BTreeSet<String>) {
+    match value {
+        Value::String(text) => {
+            if let Some(hash) = parse_reference(text) {
+                out.insert(hash.to_string());
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| references(item, out)),
+        Value::Object(map) => map.values().for_each(|item| references(item, out)),
+        _ => {}
+    }
+}
+
+/// Put attachment bodies back in place of their references. References without a body
+/// in `bodies` stay as they are.
+pub fn inline(value: &mut Value, bodies: &BTreeMap<String, String>) {
+    match value {
+        Value::String(text) => {
+            if let Some(body) = parse_reference(text).and_then(|hash| bodies.get(hash)) {
+                *text = body.clone();
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| inline(item, bodies)),
+        Value::Object(map) => map.values_mut().for_each(|item| inline(item, bodies)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn large_strings_round_trip_through_references() {
+        let output = "x".repeat(5000);
+        let image = format!("data:image/png;base64,{}", "A".repeat(2048));
+        let line = json!({"type": "response_item", "payload": {
+            "output": output, "call_id": "c1", "content": [{"image_url": image}]
+        }});
+        let mut event = SessionEvent::from_line(serde_json::to_vec(&line).unwrap());
+
+        let attachments = AttachmentPolicy { min_bytes: 4096 }.offload(&mut event);
+        assert_eq!(attachments.len(), 2);
+        let stored: Value = serde_json::from_slice(&event.raw).unwrap();
+        assert_eq!(stored["payload"]["call_id"], "c1");
+        assert_eq!(
+            stored["payload"]["output"],
+            format!("{REFERENCE_PREFIX}{}", content_hash(&output))
+        );
+
+        let mut hashes = BTreeSet::new();
+        references(&stored, &mut hashes);
+        assert_eq!(hashes.len(), 2);
+        let bodies = attachments
+            .into_iter()
+            .map(|attachment| (attachment.hash, attachment.body))
+            .collect();
+        let mut restored = stored;
+        inline(&mut restored, &bodies);
+        assert_eq!(restored, line);
+    }
+}
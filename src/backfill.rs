@@ -0,0 +1,369 @@
+use crate::config::UploadConfig;
+use crate::manifest::Manifest;
+use crate::spool::SpoolLayout;
+use crate::storage::StorageInspector;
+use crate::tail::EventParser;
+use crate::util::ensure_dir;
+use crate::watch::{SessionWatcher, SpoolSink, discover_session_files, is_recent};
+use crate::{Result, WatchConfig};
+use anyhow::Context;
+use bytesize::ByteSize;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+#[derive(Debug, Default)]
+pub struct BackfillSummary {
+    pub files: usize,
+    pub uploaded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub lines: u64,
+    pub bytes: u64,
+    pub segments: usize,
+    /// Lines whose timestamp could not be read and was carried over from the previous line.
+    pub inferred_timestamps: u64,
+    pub pending: usize,
+}
+
+enum FileOutcome {
+    Uploaded {
+        lines: u64,
+        bytes: u64,
+        segments: usize,
+        inferred_timestamps: u64,
+    },
+    Skipped(&'static str),
+}
+
+struct FileTotals {
+    lines: u64,
+    bytes: u64,
+    first_ts: Option<OffsetDateTime>,
+}
+
+pub async fn run(config: Arc<WatchConfig>, force: bool) -> Result<BackfillSummary> {
+    let dir = config
+        .session_dir
+        .clone()
+        .context("backfill requires a session directory")?;
+    let spool_layout = SpoolLayout::from_config(&config);
+    spool_layout.ensure()?;
+    ensure_dir(&config.manifest_state_dir)?;
+
+    let sink = SpoolSink::deferred(&config, &spool_layout)?;
+    let storage = match &config.upload {
+        UploadConfig::Supabase { base_url, api_key } => Some(
+            StorageInspector::new(base_url.clone(), api_key.clone(), config.bucket.clone())?
+                .with_keys(config.keyring.clone()),
+        ),
+        _ => None,
+    };
+
+    let mut files = discover_session_files(&dir.root);
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut summary = BackfillSummary {
+        files: files.len(),
+        ..Default::default()
+    };
+    for file in files {
+        // A file written to recently may still be growing, and a `watch --dir` may be
+        // following it under the same sid; importing it would mark it complete too early.
+        if !force && is_recent(file.modified, dir.idle_detach) {
+            tracing::debug!(file = %file.path.display(), "skipping recently modified session file");
+            summary.skipped += 1;
+            continue;
+        }
+        let outcome = backfill_file(
+            &config,
+            &spool_layout,
+            &sink,
+            storage.as_ref(),
+            &file.path,
+            force,
+        )
+        .await;
+        // Upload each file before reading the next so the spool never holds more than
+        // one file's segments; the session index is written once, at the end.
+        if matches!(outcome, Ok(FileOutcome::Uploaded { .. }))
+            && let Err(err) = sink.drain_queued().await
+        {
+            tracing::warn!(file = %file.path.display(), error = %err, "failed to upload session file; it stays queued");
+        }
+        match outcome {
+            Ok(FileOutcome::Uploaded {
+                lines,
+                bytes,
+                segments,
+                inferred_timestamps,
+            }) => {
+                tracing::info!(file = %file.path.display(), lines, segments, "queued session file");
+                if inferred_timestamps > 0 {
+                    tracing::warn!(
+                        file = %file.path.display(),
+                        lines = inferred_timestamps,
+                        "lines without a readable timestamp took the previous line's"
+                    );
+                }
+                summary.inferred_timestamps += inferred_timestamps;
+                summary.uploaded += 1;
+                summary.lines += lines;
+                summary.bytes += bytes;
+                summary.segments += segments;
+            }
+            Ok(FileOutcome::Skipped(reason)) => {
+                tracing::debug!(file = %file.path.display(), reason, "skipping session file");
+                summary.skipped += 1;
+            }
+            Err(err) => {
+                tracing::warn!(file = %file.path.display(), error = %err, "failed to backfill session file");
+                summary.failed += 1;
+            }
+        }
+    }
+
+    if let Err(err) = sink.drain().await {
+        tracing::warn!(error = %err, "failed to upload all queued items");
+    }
+    summary.pending = sink.pending().await?;
+    Ok(summary)
+}
+
+async fn backfill_file(
+    config: &WatchConfig,
+    spool_layout: &SpoolLayout,
+    sink: &SpoolSink,
+    storage: Option<&StorageInspector>,
+    path: &Path,
+    force: bool,
+) -> Result<FileOutcome> {
+    let mut session_config = config.for_session_file(path)?;
+    let totals = scan_totals(path, config)?;
+    if totals.lines == 0 {
+        return Ok(FileOutcome::Skipped("empty"));
+    }
+
+    let state_path = config
+        .manifest_state_dir
+        .join(format!("{}.json", session_config.sid));
+    let existing = match storage {
+        Some(storage) => storage
+            .fetch_manifest_if_exists(&config.root_prefix, &session_config.sid)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::warn!(sid = %session_config.sid, error = %err, "failed to fetch remote manifest");
+                None
+            }),
+        None if state_path.exists() => Some(Manifest::load_or_new(&state_path, &session_config)?),
+        None => None,
+    };
+    if !force
+        && let Some(existing) = &existing
+        && totals.covered_by(existing)
+    {
+        return Ok(FileOutcome::Skipped("already uploaded"));
+    }
+
+    // Re-import from scratch: any earlier manifest only covers part of the file, and
+    // upserting from seq 1 replaces its segments instead of appending duplicates.
+    if state_path.exists() {
+        std::fs::remove_file(&state_path)
+            .with_context(|| format!("failed to reset manifest state {}", state_path.display()))?;
+    }
+    if let Some(first_ts) = totals.first_ts {
+        session_config.created_at = first_ts;
+    }
+
+    let mut watcher = SessionWatcher::open(Arc::new(session_config), spool_layout, 0, None).await?;
+    watcher.poll(sink).await?;
+    watcher.complete(sink).await?;
+
+    let manifest = watcher.manifest();
+    Ok(FileOutcome::Uploaded {
+        lines: manifest.total_lines(),
+        bytes: manifest.total_bytes_uncompressed(),
+        segments: manifest.segments.len(),
+        inferred_timestamps: manifest.stats.inferred_timestamps,
+    })
+}
+
+impl FileTotals {
+    /// Whether a manifest already holds at least this many lines and bytes.
+    fn covered_by(&self, manifest: &Manifest) -> bool {
+        manifest.total_lines() >= self.lines && manifest.total_bytes_uncompressed() >= self.bytes
+    }
+}
+
+/// Count lines and bytes the way `SegmentWriter` does, so totals compare against manifests.
+fn scan_totals(path: &Path, config: &WatchConfig) -> Result<FileTotals> {
+    let mut parser = EventParser::from_config(config);
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open session file {}", path.display()))?;
+    let mut totals = FileTotals {
+        lines: 0,
+        bytes: 0,
+        first_ts: None,
+    };
+    for line in BufReader::new(file).split(b'\n') {
+        let mut line = line?;
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        // Redaction, event rules and attachments change what is stored, so count what
+        // they hand on.
+        let event = parser.parse(line);
+        if totals.first_ts.is_none() && !event.timestamp_inferred {
+            totals.first_ts = Some(event.timestamp);
+        }
+        let Some(mut event) = (match &config.event_filter {
+            Some(filter) => filter.apply(event),
+            None => Some(event),
+        }) else {
+            continue;
+        };
+        if let Some(policy) = config.attachments {
+            policy.offload(&mut event);
+        }
+        totals.lines += 1;
+        totals.bytes += event.raw.len() as u64 + 1;
+    }
+    Ok(totals)
+}
+
+impl fmt::Display for BackfillSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "backfill: {} files, {} uploaded, {} skipped, {} failed",
+            self.files, self.uploaded, self.skipped, self.failed
+        )?;
+        write!(
+            f,
+            "  {} lines, {} uncompressed, {} segments, {} items still queued",
+            self.lines,
+            ByteSize(self.bytes),
+            self.segments,
+            self.pending
+        )?;
+        if self.inferred_timestamps > 0 {
+            write!(
+                f,
+                "\n  {} lines had no readable timestamp and took the previous line's",
+                self.inferred_timestamps
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Cli, Command};
+    use clap::Parser;
+    use std::io::Write;
+    use std::time::{Duration, SystemTime};
+
+    const SID: &str = "0199b14b-f650-7c52-93bd-b226acca5ff5";
+
+    fn config(root: &Path) -> Arc<WatchConfig> {
+        let cli = Cli::try_parse_from([
+            "agent-uploader".as_ref(),
+            "backfill".as_ref(),
+            "--dir".as_ref(),
+            root.join("sessions").as_os_str(),
+            "--dry-run".as_ref(),
+            "--spool-dir".as_ref(),
+            root.join("spool").as_os_str(),
+        ])
+        .expect("valid args");
+        let Command::Backfill(args) = cli.command else {
+            panic!("expected backfill command");
+        };
+        Arc::new(WatchConfig::from_backfill_args(*args).expect("config"))
+    }
+
+    fn append(path: &Path, lines: &[&str], age: Duration) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        for line in lines {
+            writeln!(file, "{line}").unwrap();
+        }
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn state(config: &WatchConfig) -> Manifest {
+        let path = config.manifest_state_dir.join(format!("{SID}.json"));
+        Manifest::load_or_new(&path, config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reimports_grown_files_from_the_first_segment() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path());
+        std::fs::create_dir_all(root.path().join("sessions")).unwrap();
+        let file = root.path().join(format!("sessions/rollout-{SID}.jsonl"));
+        let old = Duration::from_secs(3 * 3600);
+        append(
+            &file,
+            &[
+                r#"{"timestamp":"2025-10-04T15:16:09Z","type":"session_meta","payload":{}}"#,
+                "",
+                r#"{"timestamp":"2025-10-04T15:16:10Z","type":"event_msg","payload":{}}"#,
+            ],
+            old,
+        );
+
+        let summary = run(config.clone(), false).await.unwrap();
+        assert_eq!((summary.uploaded, summary.lines), (1, 2));
+        let totals = scan_totals(&file, &config).unwrap();
+        assert!(totals.covered_by(&state(&config)));
+
+        let summary = run(config.clone(), false).await.unwrap();
+        assert_eq!((summary.uploaded, summary.skipped), (0, 1));
+
+        append(
+            &file,
+            &[r#"{"timestamp":"2025-10-04T15:16:11Z","type":"event_msg","payload":{}}"#],
+            old,
+        );
+        assert!(
+            !scan_totals(&file, &config)
+                .unwrap()
+                .covered_by(&state(&config))
+        );
+        let summary = run(config.clone(), false).await.unwrap();
+        assert_eq!(summary.uploaded, 1);
+        let manifest = state(&config);
+        assert_eq!(manifest.segments[0].seq, 1);
+        assert_eq!(manifest.total_lines(), 3);
+        assert_eq!(summary.pending, 0);
+    }
+
+    #[tokio::test]
+    async fn recently_modified_files_need_force() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path());
+        std::fs::create_dir_all(root.path().join("sessions")).unwrap();
+        let file = root.path().join(format!("sessions/rollout-{SID}.jsonl"));
+        append(
+            &file,
+            &[r#"{"timestamp":"2025-10-04T15:16:09Z","type":"event_msg","payload":{}}"#],
+            Duration::ZERO,
+        );
+
+        let summary = run(config.clone(), false).await.unwrap();
+        assert_eq!((summary.uploaded, summary.skipped), (0, 1));
+        let summary = run(config, true).await.unwrap();
+        assert_eq!(summary.uploaded, 1);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::config::SegmentCacheConfig;
+use crate::manifest::{Manifest, SegmentEntry};
+use anyhow::{Context, Result};
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+
+/// Caches segment objects for the UI server: decoded lines in memory, raw objects on disk.
+/// Entries are keyed by object path plus the segment's checksum, so a rewritten object
+/// (a newer preview, a re-imported session) never serves stale lines.
+pub struct SegmentCache {
+    memory: Option<Mutex<SizedLru<String, Arc<Vec<Value>>>>>,
+    disk: Option<DiskCache>,
+}
+
+struct DiskCache {
+    dir: PathBuf,
+    index: Mutex<SizedLru<String, ()>>,
+}
+
+/// LRU bounded by the summed size of its values rather than their count.
+struct SizedLru<K: Hash + Eq, V> {
+    entries: LruCache<K, (V, u64)>,
+    bytes: u64,
+    capacity: u64,
+}
+
+impl SegmentCache {
+    pub async fn open(config: &SegmentCacheConfig) -> Result<Self> {
+        let memory =
+            (config.memory_bytes > 0).then(|| Mutex::new(SizedLru::new(config.memory_bytes)));
+        let disk = match (&config.dir, config.disk_bytes) {
+            (Some(dir), capacity) if capacity > 0 => Some(DiskCache::open(dir, capacity).await?),
+            _ => None,
+        };
+        Ok(Self { memory, disk })
+    }
+
+    /// Cache key for a segment, or `None` when its content can change under the same
+    /// path without a checksum to tell (previews from before checksums were recorded).
+    pub fn key(object_path: &str, segment: &SegmentEntry, manifest: &Manifest) -> Option<String> {
+        let version = match &segment.checksum {
+            Some(checksum) => checksum.clone(),
+            None if manifest
+                .preview
+                .as_ref()
+                .is_some_and(|p| p.path == segment.path) =>
+            {
+                return None;
+            }
+            // Closed segments are immutable once listed in the manifest.
+            None => format!("{}-{}", segment.lines, segment.bytes_gzip),
+        };
+        Some(format!("{object_path}#{version}"))
+    }
+
+    /// Return cached lines for `key`, falling back to the disk copy and then `fetch`,
+    /// which yields the raw object to be decoded with `decode`. A fetched object whose
+    /// sha256 differs from `checksum` (the bucket already holds a newer version) is
+    /// returned uncached, so it cannot be stored under the old version's key.
+    pub async fn get_or_fetch<F, Fut, D>(
+        &self,
+        key: &str,
+        checksum: Option<&str>,
+        fetch: F,
+        decode: D,
+    ) -> Result<Arc<Vec<Value>>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<u8>>>,
+        D: Fn(Vec<u8>) -> Result<Vec<Value>>,
+    {
+        if let Some(memory) = &self.memory
+            && let Some(lines) = memory.lock().get(key)
+        {
+            return Ok(lines);
+        }
+
+        let raw = match self.read_disk(key).await {
+            Some(raw) => raw,
+            None => {
+                let raw = fetch().await?;
+                if let Some(expected) = checksum
+                    && hex::encode(Sha256::digest(&raw)) != expected
+                {
+                    tracing::debug!(
+                        key,
+                        "segment object does not match its checksum; not caching"
+                    );
+                    return Ok(Arc::new(decode(raw)?));
+                }
+                self.write_disk(key, &raw).await;
+                raw
+            }
+        };
+        let lines = Arc::new(decode(raw)?);
+        if let Some(memory) = &self.memory {
+            memory
+                .lock()
+                .put(key.to_string(), lines.clone(), decoded_size(&lines));
+        }
+        Ok(lines)
+    }
+
+    async fn read_disk(&self, key: &str) -> Option<Vec<u8>> {
+        let disk = self.disk.as_ref()?;
+        let name = DiskCache::file_name(key);
+        disk.index.lock().get(&name)?;
+        match fs::read(disk.dir.join(&name)).await {
+            Ok(raw) => Some(raw),
+            Err(err) => {
+                tracing::debug!(error = %err, "dropping unreadable segment cache entry");
+                disk.index.lock().remove(&name);
+                None
+            }
+        }
+    }
+
+    /// Best effort: a failed cache write only costs a later re-download.
+    async fn write_disk(&self, key: &str, raw: &[u8]) {
+        let Some(disk) = &self.disk else {
+            return;
+        };
+        if let Err(err) = disk.insert(key, raw).await {
+            tracing::warn!(error = %err, "failed to write segment cache entry");
+        }
+    }
+}
+
+impl DiskCache {
+    async fn open(dir: &Path, capacity: u64) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("failed to create segment cache {}", dir.display()))?;
+
+        // Seed the index oldest-first so the least recently written files are evicted first.
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if !meta.is_file() {
+                continue;
+            }
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if name.ends_with(".tmp") {
+                let _ = fs::remove_file(entry.path()).await;
+                continue;
+            }
+            files.push((meta.modified().ok(), name, meta.len()));
+        }
+        files.sort();
+
+        let mut index = SizedLru::new(capacity);
+        let mut evicted = Vec::new();
+        for (_, name, size) in files {
+            evicted.extend(index.put(name, (), size));
+        }
+        let cache = Self {
+            dir: dir.to_path_buf(),
+            index: Mutex::new(index),
+        };
+        cache.remove_files(evicted).await;
+        Ok(cache)
+    }
+
+    fn file_name(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    async fn insert(&self, key: &str, raw: &[u8]) -> Result<()> {
+        let name = Self::file_name(key);
+        let path = self.dir.join(&name);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, raw).await?;
+        fs::rename(&tmp, &path).await?;
+        let evicted = self.index.lock().put(name, (), raw.len() as u64);
+        self.remove_files(evicted).await;
+        Ok(())
+    }
+
+    async fn remove_files(&self, names: Vec<String>) {
+        for name in names {
+            let _ = fs::remove_file(self.dir.join(name)).await;
+        }
+    }
+}
+
+/// Approximate heap footprint of decoded lines. `Value` trees take several times the
+/// bytes of the JSONL they came from, so this, not the segment's size, is what counts
+/// against the memory cap.
+fn decoded_size(lines: &[Value]) -> u64 {
+    fn value_size(value: &Value) -> usize {
+        size_of::<Value>()
+            + match value {
+                Value::String(s) => s.capacity(),
+                Value::Array(items) => items.iter().map(value_size).sum(),
+                Value::Object(map) => map
+                    .iter()
+                    .map(|(key, value)| size_of::<String>() + key.capacity() + value_size(value))
+                    .sum(),
+                _ => 0,
+            }
+    }
+    lines.iter().map(value_size).sum::<usize>() as u64
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> SizedLru<K, V> {
+    fn new(capacity: u64) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            bytes: 0,
+            capacity,
+        }
+    }
+
+    fn get<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.get(key).map(|(value, _)| value.clone())
+    }
+
+    fn remove<Q>(&mut self, key: &Q)
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some((_, size)) = self.entries.pop(key) {
+            self.bytes -= size;
+        }
+    }
+
+    /// Insert and return the keys evicted to get back under capacity. A value larger
+    /// than the whole cache is not kept.
+    fn put(&mut self, key: K, value: V, size: u64) -> Vec<K> {
+        let mut evicted = Vec::new();
+        if size > self.capacity {
+            self.remove(&key);
+            evicted.push(key);
+            return evicted;
+        }
+        if let Some((_, old)) = self.entries.put(key, (value, size)) {
+            self.bytes -= old;
+        }
+        self.bytes += size;
+        while self.bytes > self.capacity {
+            let Some((key, (_, size))) = self.entries.pop_lru() else {
+                break;
+            };
+            self.bytes -= size;
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn sized_lru_evicts_least_recent_by_weight() {
+        let mut lru = SizedLru::new(10);
+        assert!(lru.put("a", 1, 4).is_empty());
+        assert!(lru.put("b", 2, 4).is_empty());
+        lru.get("a");
+        assert_eq!(lru.put("c", 3, 4), vec!["b"]);
+        assert_eq!(lru.bytes, 8);
+        // Replacing a key swaps its weight instead of adding to it.
+        assert!(lru.put("a", 1, 6).is_empty());
+        assert_eq!(lru.bytes, 10);
+        assert_eq!(lru.put("big", 4, 11), vec!["big"]);
+        assert_eq!(lru.get("big"), None);
+        assert_eq!(lru.bytes, 10);
+    }
+
+    #[tokio::test]
+    async fn disk_layer_is_reseeded_oldest_first_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SegmentCacheConfig {
+            dir: Some(dir.path().to_path_buf()),
+            memory_bytes: 0,
+            disk_bytes: 10,
+        };
+        let cache = SegmentCache::open(&config).await.unwrap();
+        for key in ["old", "mid", "new"] {
+            cache.write_disk(key, b"1234").await;
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        std::fs::write(dir.path().join("partial.tmp"), b"x").unwrap();
+
+        let reopened = SegmentCache::open(&config).await.unwrap();
+        assert!(!dir.path().join("partial.tmp").exists());
+        // Writing "new" evicted "old"; the reopened index still holds the other two.
+        assert_eq!(reopened.read_disk("old").await, None);
+        assert_eq!(
+            reopened.read_disk("mid").await.as_deref(),
+            Some(&b"1234"[..])
+        );
+        assert_eq!(
+            reopened.read_disk("new").await.as_deref(),
+            Some(&b"1234"[..])
+        );
+
+        let smaller = SegmentCacheConfig {
+            disk_bytes: 4,
+            ..config
+        };
+        let shrunk = SegmentCache::open(&smaller).await.unwrap();
+        assert_eq!(shrunk.read_disk("mid").await, None);
+        assert!(shrunk.read_disk("new").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn objects_that_fail_their_checksum_are_not_cached() {
+        let config = SegmentCacheConfig {
+            dir: None,
+            memory_bytes: 1 << 20,
+            disk_bytes: 0,
+        };
+        let cache = SegmentCache::open(&config).await.unwrap();
+        let fetches = AtomicUsize::new(0);
+        let raw = b"{\"n\":1}\n".to_vec();
+        let decode = |raw: Vec<u8>| Ok(vec![serde_json::from_slice::<Value>(&raw)?]);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(raw.clone())
+        };
+
+        for _ in 0..2 {
+            let lines = cache
+                .get_or_fetch("seg#stale", Some("not-the-hash"), fetch, decode)
+                .await
+                .unwrap();
+            assert_eq!(*lines, vec![json!({"n": 1})]);
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        let checksum = hex::encode(Sha256::digest(&raw));
+        for _ in 0..2 {
+            cache
+                .get_or_fetch("seg#fresh", Some(&checksum), fetch, decode)
+                .await
+                .unwrap();
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    }
+}
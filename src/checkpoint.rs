@@ -0,0 +1,274 @@
+use crate::tail::CheckpointTrigger;
+use crate::util::{load_json_rules, validate_pointer};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+/// One way an event can become a checkpoint. A rule fires when the event's `type`
+/// matches (if given) and every predicate in `when` holds; the first matching rule wins.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckpointRule {
+    /// Used as the label when none of the `label` pointers yield one.
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub when: Vec<Predicate>,
+    /// JSON pointers tried in order; the first non-empty string is used.
+    #[serde(default)]
+    pub label: Vec<String>,
+    #[serde(default)]
+    pub git_commit: Vec<String>,
+    #[serde(default)]
+    pub branch: Vec<String>,
+    /// Pointers to the value stored in the checkpoint object; defaults to the whole event.
+    #[serde(default)]
+    pub payload: Vec<String>,
+    /// Stored when no `payload` pointer resolves; only built-in rules set it.
+    #[serde(skip)]
+    pub payload_fallback: Option<Value>,
+    /// Close the active segment at this event so the checkpoint lands on a segment
+    /// boundary. Otherwise the checkpoint is recorded when its segment next rotates.
+    #[serde(default = "default_rotate")]
+    pub rotate: bool,
+}
+
+/// A test against the value at a JSON pointer. With no condition set, the pointer only
+/// has to resolve.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Predicate {
+    pub pointer: String,
+    #[serde(default)]
+    pub equals: Option<Value>,
+    #[serde(default)]
+    pub exists: Option<bool>,
+    /// Substring of a string value.
+    #[serde(default)]
+    pub contains: Option<String>,
+}
+
+/// The checkpoint rules in effect. Without a rules file these are the format adapter's
+/// built-in rules, e.g. Codex `compacted` events.
+#[derive(Debug, Clone)]
+pub struct CheckpointRules {
+    rules: Arc<Vec<CheckpointRule>>,
+}
+
+fn default_rotate() -> bool {
+    true
+}
+
+impl CheckpointRules {
+    pub fn new(rules: Vec<CheckpointRule>) -> Self {
+        Self {
+            rules: Arc::new(rules),
+        }
+    }
+
+    /// The built-in rule for Codex `compacted` events.
+    pub fn codex() -> Self {
+        Self::new(vec![CheckpointRule::compacted()])
+    }
+
+    pub fn none() -> Self {
+        Self::new(Vec::new())
+    }
+
+    /// Load a JSON array of rules; it replaces the format's built-in rules.
+    pub fn load(path: &Path) -> Result<Self> {
+        let rules: Vec<CheckpointRule> = load_json_rules(path, "checkpoint rules")?;
+        for rule in &rules {
+            rule.validate()
+                .with_context(|| format!("invalid checkpoint rule {:?}", rule.name))?;
+        }
+        Ok(Self::new(rules))
+    }
+
+    pub fn rules(&self) -> &[CheckpointRule] {
+        &self.rules
+    }
+
+    pub fn evaluate(&self, event_type: Option<&str>, value: &Value) -> Option<CheckpointTrigger> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(event_type, value))
+            .map(|rule| rule.trigger(value))
+    }
+}
+
+impl CheckpointRule {
+    /// Codex writes `{"type":"compacted"}` when it compacts the conversation history.
+    fn compacted() -> Self {
+        let pointers = |list: &[&str]| list.iter().map(|p| p.to_string()).collect();
+        Self {
+            name: "compacted".to_string(),
+            event_type: Some("compacted".to_string()),
+            when: Vec::new(),
+            label: pointers(&[
+                "/checkpoint/label",
+                "/checkpoint/summary",
+                "/detail/label",
+                "/detail/summary",
+                "/note",
+            ]),
+            git_commit: pointers(&[
+                "/checkpoint/git_commit",
+                "/checkpoint/git",
+                "/detail/git_commit",
+                "/detail/git",
+            ]),
+            branch: pointers(&["/checkpoint/branch", "/detail/branch"]),
+            payload: pointers(&["/checkpoint", "/detail"]),
+            // A bare `{"type":"compacted"}` has always been stored as `{}`.
+            payload_fallback: Some(Value::Object(Default::default())),
+            rotate: true,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let pointers = self
+            .when
+            .iter()
+            .map(|predicate| &predicate.pointer)
+            .chain(&self.label)
+            .chain(&self.git_commit)
+            .chain(&self.branch)
+            .chain(&self.payload);
+        for pointer in pointers {
+            validate_pointer(pointer)?;
+        }
+        Ok(())
+    }
+
+    fn matches(&self, event_type: Option<&str>, value: &Value) -> bool {
+        if let Some(expected) = &self.event_type
+            && event_type != Some(expected.as_str())
+        {
+            return false;
+        }
+        self.when.iter().all(|predicate| predicate.holds(value))
+    }
+
+    fn trigger(&self, value: &Value) -> CheckpointTrigger {
+        CheckpointTrigger {
+            label: first_string(value, &self.label).or_else(|| Some(self.name.clone())),
+            git_commit: first_string(value, &self.git_commit),
+            branch: first_string(value, &self.branch),
+            payload: self
+                .payload
+                .iter()
+                .find_map(|pointer| value.pointer(pointer))
+                .or(self.payload_fallback.as_ref())
+                .cloned(),
+            rotate: self.rotate,
+        }
+    }
+}
+
+impl Predicate {
+    /// Whether no value can satisfy both this and `other`, judged from `equals` and
+    /// `exists` on the same pointer only.
+    pub(crate) fn contradicts(&self, other: &Predicate) -> bool {
+        if self.pointer != other.pointer {
+            return false;
+        }
+        let requires = |predicate: &Predicate| {
+            predicate.equals.is_some()
+                || predicate.contains.is_some()
+                || predicate.exists == Some(true)
+        };
+        match (&self.equals, &other.equals) {
+            (Some(a), Some(b)) if a != b => true,
+            _ => {
+                (self.exists == Some(false) && requires(other))
+                    || (other.exists == Some(false) && requires(self))
+            }
+        }
+    }
+
+    pub(crate) fn holds(&self, value: &Value) -> bool {
+        let found = value.pointer(&self.pointer);
+        if let Some(exists) = self.exists
+            && found.is_some() != exists
+        {
+            return false;
+        }
+        if let Some(expected) = &self.equals
+            && found != Some(expected)
+        {
+            return false;
+        }
+        if let Some(needle) = &self.contains
+            && !found
+                .and_then(Value::as_str)
+                .is_some_and(|text| text.contains(needle.as_str()))
+        {
+            return false;
+        }
+        self.exists.is_some() || found.is_some()
+    }
+}
+
+fn first_string(value: &Value, pointers: &[String]) -> Option<String> {
+    pointers
+        .iter()
+        .filter_map(|pointer| value.pointer(pointer).and_then(Value::as_str))
+        .find(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn builtin_rule_reads_compacted_events() {
+        let event = json!({
+            "type": "compacted",
+            "checkpoint": {"summary": "refactor done", "git": "abc123", "branch": "main"}
+        });
+        let trigger = CheckpointRules::codex()
+            .evaluate(Some("compacted"), &event)
+            .expect("compacted is a checkpoint");
+        assert_eq!(trigger.label.as_deref(), Some("refactor done"));
+        assert_eq!(trigger.git_commit.as_deref(), Some("abc123"));
+        assert_eq!(trigger.branch.as_deref(), Some("main"));
+        assert!(trigger.rotate);
+
+        let bare = CheckpointRules::codex()
+            .evaluate(Some("compacted"), &json!({"type": "compacted"}))
+            .unwrap();
+        assert_eq!(bare.label.as_deref(), Some("compacted"));
+        assert_eq!(bare.payload, Some(json!({})));
+    }
+
+    #[test]
+    fn predicates_select_events() {
+        let rules: Vec<CheckpointRule> = serde_json::from_value(json!([{
+            "name": "plan",
+            "type": "response_item",
+            "when": [
+                {"pointer": "/payload/name", "equals": "update_plan"},
+                {"pointer": "/payload/arguments", "contains": "completed"}
+            ],
+            "label": ["/payload/call_id"],
+            "rotate": false
+        }]))
+        .unwrap();
+        let rules = CheckpointRules::new(rules);
+
+        let plan = json!({"type": "response_item", "payload": {
+            "name": "update_plan", "arguments": "{\"status\":\"completed\"}", "call_id": "call_1"
+        }});
+        let trigger = rules.evaluate(Some("response_item"), &plan).unwrap();
+        assert_eq!(trigger.label.as_deref(), Some("call_1"));
+        assert!(!trigger.rotate);
+
+        let other = json!({"type": "response_item", "payload": {"name": "shell"}});
+        assert!(rules.evaluate(Some("response_item"), &other).is_none());
+        assert!(rules.evaluate(Some("compacted"), &json!({})).is_none());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::util::ensure_dir;
+use anyhow::{Context, Result, bail};
+use async_compression::Level;
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use clap::ValueEnum;
+use flate2::read::GzDecoder;
+use std::io::{Read, Write};
+use std::path::Path;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Codec selected with `--compression`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CompressionKind {
+    #[default]
+    Gzip,
+    Zstd,
+    None,
+}
+
+/// How closed segments, previews and attachments are compressed before upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub kind: CompressionKind,
+    /// Codec level; the codec's default when unset.
+    pub level: Option<i32>,
+}
+
+impl Compression {
+    pub const NONE: Self = Self {
+        kind: CompressionKind::None,
+        level: None,
+    };
+
+    pub fn new(kind: CompressionKind, level: Option<i32>) -> Result<Self> {
+        let range = match kind {
+            CompressionKind::Gzip => 0..=9,
+            CompressionKind::Zstd => 1..=22,
+            CompressionKind::None if level.is_some() => {
+                bail!("--compression-level needs --compression gzip or zstd")
+            }
+            CompressionKind::None => return Ok(Self::NONE),
+        };
+        if let Some(level) = level
+            && !range.contains(&level)
+        {
+            bail!(
+                "--compression-level for {} must be between {} and {}",
+                kind.name(),
+                range.start(),
+                range.end()
+            );
+        }
+        Ok(Self { kind, level })
+    }
+
+    pub fn is_enabled(self) -> bool {
+        self.kind != CompressionKind::None
+    }
+
+    /// Appended to object names: `.gz`, `.zst`, or nothing.
+    pub fn extension(self) -> &'static str {
+        match self.kind {
+            CompressionKind::Gzip => ".gz",
+            CompressionKind::Zstd => ".zst",
+            CompressionKind::None => "",
+        }
+    }
+
+    /// `Content-Encoding` of compressed objects, also recorded in manifest entries.
+    pub fn content_encoding(self) -> Option<&'static str> {
+        self.is_enabled().then(|| self.kind.name())
+    }
+
+    /// Compress `source` into `dest`; returns the compressed size.
+    pub async fn compress_file(self, source: &Path, dest: &Path) -> Result<u64> {
+        if let Some(parent) = dest.parent() {
+            ensure_dir(parent)?;
+        }
+        let mut reader = File::open(source)
+            .await
+            .with_context(|| format!("failed to open {} for compression", source.display()))?;
+        let dest_file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(dest)
+            .await
+            .with_context(|| format!("failed to create {}", dest.display()))?;
+        let level = self.level.map_or(Level::Default, Level::Precise);
+        let mut encoder: Box<dyn AsyncWrite + Unpin + Send> = match self.kind {
+            CompressionKind::Gzip => Box::new(GzipEncoder::with_quality(dest_file, level)),
+            CompressionKind::Zstd => Box::new(ZstdEncoder::with_quality(dest_file, level)),
+            CompressionKind::None => Box::new(dest_file),
+        };
+        tokio::io::copy(&mut reader, &mut encoder).await?;
+        encoder.shutdown().await?;
+        Ok(fs::metadata(dest).await?.len())
+    }
+
+    /// Compress an in-memory object such as an attachment.
+    pub fn compress(self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self.kind {
+            CompressionKind::Gzip => {
+                let level = self.level.map_or(flate2::Compression::default(), |level| {
+                    flate2::Compression::new(level as u32)
+                });
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            CompressionKind::Zstd => Ok(zstd::encode_all(bytes, self.level.unwrap_or(0))?),
+            CompressionKind::None => Ok(bytes.to_vec()),
+        }
+    }
+}
+
+impl CompressionKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::None => "none",
+        }
+    }
+
+    /// The codec named by a manifest `compression` field, or else by the object name's
+    /// extension.
+    pub fn detect(path: &str, encoding: Option<&str>) -> Self {
+        match encoding {
+            Some("gzip") => Self::Gzip,
+            Some("zstd") => Self::Zstd,
+            _ if path.ends_with(".gz") => Self::Gzip,
+            _ if path.ends_with(".zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+
+    pub fn decompress(self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut out = Vec::new();
+                GzDecoder::new(bytes.as_slice())
+                    .read_to_end(&mut out)
+                    .context("invalid gzip data")?;
+                Ok(out)
+            }
+            Self::Zstd => zstd::decode_all(bytes.as_slice()).context("invalid zstd data"),
+            Self::None => Ok(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codecs_round_trip_and_are_detected() {
+        let body = b"{\"type\":\"event_msg\"}\n".repeat(64);
+        for kind in [
+            CompressionKind::Gzip,
+            CompressionKind::Zstd,
+            CompressionKind::None,
+        ] {
+            let compression = Compression::new(kind, None).unwrap();
+            let name = format!("session-000001.jsonl{}", compression.extension());
+            let packed = compression.compress(&body).unwrap();
+            let detected = CompressionKind::detect(&name, None);
+            assert_eq!(detected, kind);
+            assert_eq!(detected.decompress(packed).unwrap(), body);
+        }
+        assert_eq!(
+            CompressionKind::detect("segments/x.enc", Some("zstd")),
+            CompressionKind::Zstd
+        );
+        assert!(Compression::new(CompressionKind::Zstd, Some(23)).is_err());
+        assert!(Compression::new(CompressionKind::None, Some(3)).is_err());
+    }
+}
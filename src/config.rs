@@ -1,10 +1,21 @@
-use crate::util::{expand_path, generate_sid};
+use crate::attachments::AttachmentPolicy;
+use crate::checkpoint::CheckpointRules;
+use crate::compression::{Compression, CompressionKind};
+use crate::crypto::Keyring;
+use crate::filter::EventFilter;
+use crate::format::SessionFormat;
+use crate::redact::Redactor;
+use crate::tail::EventFields;
+use crate::usage::Pricing;
+use crate::util::{expand_path, generate_sid, validate_pointer};
 use anyhow::{Context, Result, bail};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 use time::Duration as TimeDuration;
-use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
 const DEFAULT_SEG_BYTES: usize = 8 * 1024 * 1024; // 8 MiB
@@ -12,8 +23,15 @@ const DEFAULT_SEG_LINES: usize = 10_000;
 const DEFAULT_SEG_WALL_MS: u64 = 600_000; // 10 minutes
 const DEFAULT_POLL_MS: u64 = 500;
 const DEFAULT_CONCURRENCY: usize = 2;
+const DEFAULT_BACKFILL_CONCURRENCY: usize = 8;
 const DEFAULT_ROOT_PREFIX: &str = "sessions";
 const DEFAULT_UI_PORT: u16 = 4333;
+const DEFAULT_SCAN_MS: u64 = 2_000;
+const DEFAULT_IDLE_DETACH_MS: u64 = 1_800_000; // 30 minutes
+const DEFAULT_HEARTBEAT_MS: u64 = 15_000;
+const DEFAULT_UI_CACHE_MB: u64 = 64;
+const DEFAULT_UI_CACHE_DISK_MB: u64 = 512;
+const DEFAULT_UI_MIRROR_MB: u64 = 512;
 
 #[derive(Debug, Parser)]
 #[command(name = "agent-uploader", version, about = "Tail Codex sessions and mirror them to Supabase Storage", long_about = None)]
@@ -25,13 +43,17 @@ pub struct Cli {
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// Tail a session history file and mirror segments to Supabase Storage
-    Watch(WatchArgs),
+    Watch(Box<WatchArgs>),
+    /// Segment and upload every finished session file under a directory
+    Backfill(Box<BackfillArgs>),
     /// Reconstruct a session.jsonl locally from remote Storage assets
     Reload(ReloadArgs),
     /// Stream a session to stdout using remote Storage assets
     Replay(ReplayArgs),
     /// Serve the local web UI bundle for browsing sessions
     Host(HostArgs),
+    /// Report token usage and estimated cost per session
+    Usage(UsageArgs),
     /// Print the CLI version information
     Version,
 }
@@ -39,8 +61,25 @@ pub enum Command {
 #[derive(Debug, Clone, Args)]
 pub struct WatchArgs {
     /// Path to the session history file emitted by the coding agent CLI (NDJSON)
-    #[arg(long = "file", env = "AGENT_SESSION_FILE")]
-    pub session_file: PathBuf,
+    #[arg(
+        long = "file",
+        env = "AGENT_SESSION_FILE",
+        required_unless_present = "session_dir",
+        conflicts_with = "session_dir"
+    )]
+    pub session_file: Option<PathBuf>,
+
+    /// Directory scanned recursively for session files (e.g. ~/.codex/sessions)
+    #[arg(long = "dir", env = "AGENT_SESSION_DIR")]
+    pub session_dir: Option<PathBuf>,
+
+    /// Interval in milliseconds between directory scans in --dir mode
+    #[arg(long = "scan-ms", default_value_t = DEFAULT_SCAN_MS)]
+    pub scan_ms: u64,
+
+    /// Detach a file in --dir mode after this many milliseconds without new data
+    #[arg(long = "idle-detach-ms", default_value_t = DEFAULT_IDLE_DETACH_MS)]
+    pub idle_detach_ms: u64,
 
     /// Supabase Storage bucket name
     #[arg(long, env = "SUPABASE_BUCKET", default_value = "sessions")]
@@ -78,6 +117,14 @@ pub struct WatchArgs {
     #[arg(long, default_value_t = DEFAULT_CONCURRENCY)]
     pub concurrency: usize,
 
+    /// Interval in milliseconds between live.json heartbeat uploads (0 disables)
+    #[arg(long = "heartbeat-ms", default_value_t = DEFAULT_HEARTBEAT_MS)]
+    pub heartbeat_ms: u64,
+
+    /// Interval in milliseconds between uploads of the active segment preview (0 disables)
+    #[arg(long = "preview-ms", default_value_t = 0)]
+    pub preview_ms: u64,
+
     /// Verbose logging (sets RUST_LOG=debug if unset)
     #[arg(long)]
     pub verbose: bool,
@@ -86,10 +133,34 @@ pub struct WatchArgs {
     #[arg(long)]
     pub dry_run: bool,
 
-    /// Disable gzip compression for closed segments
-    #[arg(long = "no-gzip")]
+    /// Codec for closed segments, previews and attachments
+    #[arg(long, value_enum, env = "AGENT_COMPRESSION", default_value_t = CompressionKind::Gzip)]
+    pub compression: CompressionKind,
+
+    /// Compression level (gzip 0-9, zstd 1-22); the codec's default when unset
+    #[arg(long = "compression-level", env = "AGENT_COMPRESSION_LEVEL")]
+    pub compression_level: Option<i32>,
+
+    /// Disable compression for closed segments (same as --compression none)
+    #[arg(long = "no-gzip", conflicts_with_all = ["compression", "compression_level"])]
     pub no_gzip: bool,
 
+    /// Upload the current file contents, drain the spool and exit instead of tailing
+    #[arg(long, conflicts_with = "session_dir")]
+    pub once: bool,
+
+    /// Mark the session complete after this many milliseconds without new data
+    #[arg(long = "end-idle-ms")]
+    pub end_idle_ms: Option<u64>,
+
+    /// Event type that marks the session complete (repeatable)
+    #[arg(long = "end-event")]
+    pub end_events: Vec<String>,
+
+    /// Mark the session complete when the agent process with this pid exits
+    #[arg(long, conflicts_with = "session_dir")]
+    pub pid: Option<u32>,
+
     /// Override Supabase REST endpoint (https://<project>.supabase.co)
     #[arg(long = "supabase-url", env = "SUPABASE_URL")]
     pub supabase_url: Option<String>,
@@ -121,6 +192,174 @@ pub struct WatchArgs {
     /// Directory containing the built web UI assets (defaults to ./frontend/dist)
     #[arg(long = "ui-dist", env = "AGENT_UI_DIST")]
     pub ui_dist: Option<PathBuf>,
+
+    /// Directory for the UI's downloaded segment cache (defaults to <spool>/cache)
+    #[arg(long = "ui-cache-dir")]
+    pub ui_cache_dir: Option<PathBuf>,
+
+    /// Memory budget in MiB for decoded segments served by the UI (0 disables)
+    #[arg(long = "ui-cache-mb", default_value_t = DEFAULT_UI_CACHE_MB)]
+    pub ui_cache_mb: u64,
+
+    /// Disk budget in MiB for downloaded segments served by the UI (0 disables)
+    #[arg(long = "ui-cache-disk-mb", default_value_t = DEFAULT_UI_CACHE_DISK_MB)]
+    pub ui_cache_disk_mb: u64,
+
+    /// Disk budget in MiB for dry-run objects kept under <spool>/local for the UI (0 disables)
+    #[arg(long = "ui-mirror-mb", default_value_t = DEFAULT_UI_MIRROR_MB)]
+    pub ui_mirror_mb: u64,
+
+    /// JSON file of per-model token prices (USD per million tokens) for cost estimates
+    #[arg(long, env = "AGENT_PRICING_FILE")]
+    pub pricing: Option<PathBuf>,
+
+    /// Transcript format; `auto` picks one from the opening lines of each file
+    #[arg(long, value_enum, env = "AGENT_SESSION_FORMAT", default_value_t = SessionFormat::Auto)]
+    pub format: SessionFormat,
+
+    /// JSON file of checkpoint rules replacing the format's built-in rules
+    #[arg(long = "checkpoint-rules", env = "AGENT_CHECKPOINT_RULES")]
+    pub checkpoint_rules: Option<PathBuf>,
+
+    /// JSON pointer to the event timestamp (repeatable, tried in order; default from --format)
+    #[arg(long = "timestamp-pointer")]
+    pub timestamp_pointers: Vec<String>,
+
+    /// JSON pointer to the event type (repeatable, tried in order; default from --format)
+    #[arg(long = "type-pointer")]
+    pub type_pointers: Vec<String>,
+
+    /// JSON file of extra redaction rules (`[{"name", "pattern"}]`) added to the built-in detectors
+    #[arg(long = "redact-rules", env = "AGENT_REDACT_RULES")]
+    pub redact_rules: Option<PathBuf>,
+
+    /// Upload lines without redacting secrets
+    #[arg(long = "no-redact", conflicts_with = "redact_rules")]
+    pub no_redact: bool,
+
+    /// JSON file of rules that drop events, truncate long strings or strip fields before storage
+    #[arg(long = "event-rules", env = "AGENT_EVENT_RULES")]
+    pub event_rules: Option<PathBuf>,
+
+    /// Move strings of at least this many bytes into separate attachment objects
+    #[arg(long = "attachment-bytes", env = "AGENT_ATTACHMENT_BYTES")]
+    pub attachment_bytes: Option<usize>,
+
+    /// Key file (hex keys, one per line); encrypts uploaded segments, checkpoints and manifests
+    #[arg(long = "encryption-key", env = "AGENT_ENCRYPTION_KEY")]
+    pub encryption_key: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct BackfillArgs {
+    /// Directory scanned recursively for session files (e.g. ~/.codex/sessions)
+    #[arg(long = "dir", env = "AGENT_SESSION_DIR")]
+    pub session_dir: PathBuf,
+
+    /// Supabase Storage bucket name
+    #[arg(long, env = "SUPABASE_BUCKET", default_value = "sessions")]
+    pub bucket: String,
+
+    /// Root prefix prepended before the session id when storing objects
+    #[arg(long, default_value = DEFAULT_ROOT_PREFIX)]
+    pub root_prefix: String,
+
+    /// Maximum uncompressed bytes per segment before rotation
+    #[arg(long = "seg-bytes", default_value_t = DEFAULT_SEG_BYTES)]
+    pub seg_bytes: usize,
+
+    /// Maximum lines per segment before rotation
+    #[arg(long = "seg-lines", default_value_t = DEFAULT_SEG_LINES)]
+    pub seg_lines: usize,
+
+    /// Directory used to spool pending uploads
+    #[arg(long = "spool-dir")]
+    pub spool_dir: Option<PathBuf>,
+
+    /// Optional path to write manifests locally before upload
+    #[arg(long = "state-dir")]
+    pub state_dir: Option<PathBuf>,
+
+    /// Number of concurrent uploads to Storage
+    #[arg(long, default_value_t = DEFAULT_BACKFILL_CONCURRENCY)]
+    pub concurrency: usize,
+
+    /// Re-upload files even when the existing manifest already covers them, and import
+    /// files modified within --idle-detach-ms
+    #[arg(long)]
+    pub force: bool,
+
+    /// Skip files modified within this many milliseconds; they may still be written to
+    #[arg(long = "idle-detach-ms", default_value_t = DEFAULT_IDLE_DETACH_MS)]
+    pub idle_detach_ms: u64,
+
+    /// Verbose logging (sets RUST_LOG=debug if unset)
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Dry-run mode. Segment locally but skip remote uploads
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Codec for closed segments, previews and attachments
+    #[arg(long, value_enum, env = "AGENT_COMPRESSION", default_value_t = CompressionKind::Gzip)]
+    pub compression: CompressionKind,
+
+    /// Compression level (gzip 0-9, zstd 1-22); the codec's default when unset
+    #[arg(long = "compression-level", env = "AGENT_COMPRESSION_LEVEL")]
+    pub compression_level: Option<i32>,
+
+    /// Disable compression for closed segments (same as --compression none)
+    #[arg(long = "no-gzip", conflicts_with_all = ["compression", "compression_level"])]
+    pub no_gzip: bool,
+
+    /// Override Supabase REST endpoint (https://<project>.supabase.co)
+    #[arg(long = "supabase-url", env = "SUPABASE_URL")]
+    pub supabase_url: Option<String>,
+
+    /// Service or anon key for Supabase Storage REST
+    #[arg(long = "supabase-key", env = "SUPABASE_KEY")]
+    pub supabase_key: Option<String>,
+
+    /// Optional presigned upload URL template; bypasses Supabase REST
+    #[arg(long = "upload-url")]
+    pub upload_url: Option<String>,
+
+    /// Transcript format; `auto` picks one from the opening lines of each file
+    #[arg(long, value_enum, env = "AGENT_SESSION_FORMAT", default_value_t = SessionFormat::Auto)]
+    pub format: SessionFormat,
+
+    /// JSON file of checkpoint rules replacing the format's built-in rules
+    #[arg(long = "checkpoint-rules", env = "AGENT_CHECKPOINT_RULES")]
+    pub checkpoint_rules: Option<PathBuf>,
+
+    /// JSON pointer to the event timestamp (repeatable, tried in order; default from --format)
+    #[arg(long = "timestamp-pointer")]
+    pub timestamp_pointers: Vec<String>,
+
+    /// JSON pointer to the event type (repeatable, tried in order; default from --format)
+    #[arg(long = "type-pointer")]
+    pub type_pointers: Vec<String>,
+
+    /// JSON file of extra redaction rules (`[{"name", "pattern"}]`) added to the built-in detectors
+    #[arg(long = "redact-rules", env = "AGENT_REDACT_RULES")]
+    pub redact_rules: Option<PathBuf>,
+
+    /// Upload lines without redacting secrets
+    #[arg(long = "no-redact", conflicts_with = "redact_rules")]
+    pub no_redact: bool,
+
+    /// JSON file of rules that drop events, truncate long strings or strip fields before storage
+    #[arg(long = "event-rules", env = "AGENT_EVENT_RULES")]
+    pub event_rules: Option<PathBuf>,
+
+    /// Move strings of at least this many bytes into separate attachment objects
+    #[arg(long = "attachment-bytes", env = "AGENT_ATTACHMENT_BYTES")]
+    pub attachment_bytes: Option<usize>,
+
+    /// Key file (hex keys, one per line); encrypts uploaded segments, checkpoints and manifests
+    #[arg(long = "encryption-key", env = "AGENT_ENCRYPTION_KEY")]
+    pub encryption_key: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Args, Default)]
@@ -149,6 +388,57 @@ pub struct ReplayArgs {
     pub checkpoint: String,
 }
 
+#[derive(Debug, Clone, Args)]
+pub struct UsageArgs {
+    /// Session to report (repeatable); defaults to every session
+    #[arg(long = "sid")]
+    pub sids: Vec<String>,
+
+    /// Only sessions updated on or after this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Break each session down by checkpoint
+    #[arg(long)]
+    pub checkpoints: bool,
+
+    /// Print the report as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+
+    /// JSON file of per-model token prices (USD per million tokens) for cost estimates
+    #[arg(long, env = "AGENT_PRICING_FILE")]
+    pub pricing: Option<PathBuf>,
+
+    /// Supabase Storage bucket name
+    #[arg(long, env = "SUPABASE_BUCKET", default_value = "sessions")]
+    pub bucket: String,
+
+    /// Root prefix prepended before the session id when storing objects
+    #[arg(long, default_value = DEFAULT_ROOT_PREFIX)]
+    pub root_prefix: String,
+
+    /// Spool directory of the uploader whose sessions to report without Supabase
+    #[arg(long = "spool-dir")]
+    pub spool_dir: Option<PathBuf>,
+
+    /// Manifest state directory to read without Supabase (defaults to <spool>/state)
+    #[arg(long = "state-dir")]
+    pub state_dir: Option<PathBuf>,
+
+    /// Override Supabase REST endpoint (https://<project>.supabase.co)
+    #[arg(long = "supabase-url", env = "SUPABASE_URL")]
+    pub supabase_url: Option<String>,
+
+    /// Service or anon key for Supabase Storage REST
+    #[arg(long = "supabase-key", env = "SUPABASE_KEY")]
+    pub supabase_key: Option<String>,
+
+    /// Key file for reading encrypted sessions
+    #[arg(long = "encryption-key", env = "AGENT_ENCRYPTION_KEY")]
+    pub encryption_key: Option<PathBuf>,
+}
+
 #[derive(Debug, Clone, Args, Default)]
 pub struct HostArgs {
     /// Directory containing static web assets to serve
@@ -179,6 +469,7 @@ pub struct HostArgs {
 #[derive(Debug, Clone)]
 pub struct WatchConfig {
     pub session_file: PathBuf,
+    pub session_dir: Option<SessionDirConfig>,
     pub bucket: String,
     pub sid: String,
     pub root_prefix: String,
@@ -186,13 +477,29 @@ pub struct WatchConfig {
     pub poll_interval: Duration,
     pub spool_dir: PathBuf,
     pub concurrency: usize,
+    pub heartbeat: Option<Duration>,
+    pub preview: Option<Duration>,
     pub verbose: bool,
     pub dry_run: bool,
-    pub gzip_enabled: bool,
+    pub once: bool,
+    pub session_end: SessionEndConfig,
+    pub compression: Compression,
     pub upload: UploadConfig,
     pub manifest_state_dir: PathBuf,
     pub created_at: OffsetDateTime,
     pub ui: UiConfig,
+    pub pricing: Pricing,
+    pub format: SessionFormat,
+    /// Replaces the format's built-in checkpoint rules when set.
+    pub checkpoints: Option<CheckpointRules>,
+    pub event_fields: EventFields,
+    /// `None` with `--no-redact`.
+    pub redactor: Option<Redactor>,
+    /// Seals uploaded objects and opens them for the UI when set.
+    pub keyring: Option<Keyring>,
+    pub event_filter: Option<EventFilter>,
+    /// `None` unless `--attachment-bytes` is set.
+    pub attachments: Option<AttachmentPolicy>,
 }
 
 #[derive(Debug, Clone)]
@@ -202,12 +509,37 @@ pub struct RotatePolicy {
     pub max_wall: TimeDuration,
 }
 
+#[derive(Debug, Clone)]
+pub struct SessionDirConfig {
+    pub root: PathBuf,
+    pub scan_interval: Duration,
+    pub idle_detach: Duration,
+}
+
+/// Signals that end a session; any one of them finalizes it and marks the manifest complete.
+#[derive(Debug, Clone, Default)]
+pub struct SessionEndConfig {
+    pub idle: Option<Duration>,
+    pub event_types: Vec<String>,
+    pub pid: Option<u32>,
+}
+
 #[derive(Debug, Clone)]
 pub struct UiConfig {
     pub enabled: bool,
     pub bind: String,
     pub port: u16,
     pub dist_dir: Option<PathBuf>,
+    pub cache: SegmentCacheConfig,
+    /// Byte budget for the dry-run mirror under `<spool>/local`; 0 keeps no copies.
+    pub mirror_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentCacheConfig {
+    pub dir: Option<PathBuf>,
+    pub memory_bytes: u64,
+    pub disk_bytes: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -228,8 +560,87 @@ impl WatchConfig {
         Self::try_from_args(args)
     }
 
+    /// Build a directory-mode config for `backfill`. Wall-clock rotation never fires while
+    /// importing, so segments are cut by bytes and lines only.
+    pub fn from_backfill_args(args: BackfillArgs) -> Result<Self> {
+        Self::try_from_args(WatchArgs {
+            session_file: None,
+            session_dir: Some(args.session_dir),
+            scan_ms: DEFAULT_SCAN_MS,
+            idle_detach_ms: args.idle_detach_ms,
+            bucket: args.bucket,
+            sid: "auto".to_string(),
+            root_prefix: args.root_prefix,
+            seg_bytes: args.seg_bytes,
+            seg_lines: args.seg_lines,
+            seg_ms: DEFAULT_SEG_WALL_MS,
+            poll_ms: DEFAULT_POLL_MS,
+            spool_dir: args.spool_dir,
+            concurrency: args.concurrency,
+            heartbeat_ms: 0,
+            preview_ms: 0,
+            verbose: args.verbose,
+            dry_run: args.dry_run,
+            compression: args.compression,
+            compression_level: args.compression_level,
+            no_gzip: args.no_gzip,
+            once: false,
+            end_idle_ms: None,
+            end_events: Vec::new(),
+            pid: None,
+            supabase_url: args.supabase_url,
+            supabase_key: args.supabase_key,
+            upload_url: args.upload_url,
+            state_dir: args.state_dir,
+            ui_disable: true,
+            ui_bind: "127.0.0.1".to_string(),
+            ui_port: DEFAULT_UI_PORT,
+            ui_dist: None,
+            ui_cache_dir: None,
+            ui_cache_mb: 0,
+            ui_cache_disk_mb: 0,
+            ui_mirror_mb: 0,
+            pricing: None,
+            format: args.format,
+            checkpoint_rules: args.checkpoint_rules,
+            timestamp_pointers: args.timestamp_pointers,
+            type_pointers: args.type_pointers,
+            redact_rules: args.redact_rules,
+            no_redact: args.no_redact,
+            encryption_key: args.encryption_key,
+            event_rules: args.event_rules,
+            attachment_bytes: args.attachment_bytes,
+        })
+    }
+
     fn try_from_args(args: WatchArgs) -> Result<Self> {
-        let session_file = expand_path(&args.session_file)?;
+        let session_dir = match args.session_dir {
+            Some(path) => {
+                let root = expand_path(&path)?;
+                let scan_interval = Duration::from_millis(args.scan_ms);
+                if scan_interval.is_zero() {
+                    bail!("scan-ms must be greater than 0");
+                }
+                let idle_detach = Duration::from_millis(args.idle_detach_ms);
+                if idle_detach.is_zero() {
+                    bail!("idle-detach-ms must be greater than 0");
+                }
+                if !args.sid.trim().eq_ignore_ascii_case("auto") {
+                    bail!("sid cannot be set with --dir; it is derived from each session file");
+                }
+                Some(SessionDirConfig {
+                    root,
+                    scan_interval,
+                    idle_detach,
+                })
+            }
+            None => None,
+        };
+        let session_file = match (&args.session_file, &session_dir) {
+            (Some(path), _) => expand_path(path)?,
+            (None, Some(dir)) => dir.root.clone(),
+            (None, None) => bail!("either --file or --dir is required"),
+        };
         let spool_dir = match args.spool_dir {
             Some(path) => expand_path(&path)?,
             None => default_spool_dir()?,
@@ -240,6 +651,43 @@ impl WatchConfig {
             None => spool_dir.join("state"),
         };
 
+        let ui_cache_dir = match args.ui_cache_dir {
+            Some(path) => expand_path(&path)?,
+            None => spool_dir.join("cache"),
+        };
+
+        let pricing = match &args.pricing {
+            Some(path) => Pricing::load(&expand_path(path)?)?,
+            None => Pricing::default(),
+        };
+
+        let checkpoints = match &args.checkpoint_rules {
+            Some(path) => Some(CheckpointRules::load(&expand_path(path)?)?),
+            None => None,
+        };
+
+        let event_fields = event_fields(args.timestamp_pointers, args.type_pointers)?;
+
+        let redactor = if args.no_redact {
+            None
+        } else {
+            let rules = args.redact_rules.as_deref().map(expand_path).transpose()?;
+            Some(Redactor::new(rules.as_deref())?)
+        };
+
+        let keyring = load_keyring(args.encryption_key.as_deref())?;
+
+        let compression = if args.no_gzip {
+            Compression::NONE
+        } else {
+            Compression::new(args.compression, args.compression_level)?
+        };
+
+        let event_filter = match &args.event_rules {
+            Some(path) => Some(EventFilter::load(&expand_path(path)?)?),
+            None => None,
+        };
+
         let ui_dist = match args.ui_dist {
             Some(path) => Some(expand_path(&path)?),
             None => default_ui_dist()?,
@@ -294,17 +742,34 @@ impl WatchConfig {
             UploadConfig::Supabase { base_url, api_key }
         };
 
+        let session_end = SessionEndConfig {
+            idle: match args.end_idle_ms {
+                Some(0) => bail!("end-idle-ms must be greater than 0"),
+                Some(ms) => Some(Duration::from_millis(ms)),
+                None => None,
+            },
+            event_types: args.end_events,
+            pid: args.pid,
+        };
+
         let created_at = OffsetDateTime::now_utc();
 
         let ui = UiConfig {
-            enabled: !args.ui_disable,
+            enabled: !args.ui_disable && !args.once,
             bind: args.ui_bind,
             port: args.ui_port,
             dist_dir: ui_dist,
+            cache: SegmentCacheConfig {
+                dir: Some(ui_cache_dir),
+                memory_bytes: args.ui_cache_mb * 1024 * 1024,
+                disk_bytes: args.ui_cache_disk_mb * 1024 * 1024,
+            },
+            mirror_bytes: args.ui_mirror_mb * 1024 * 1024,
         };
 
         Ok(Self {
             session_file,
+            session_dir,
             bucket: args.bucket,
             sid,
             root_prefix: args.root_prefix,
@@ -312,19 +777,151 @@ impl WatchConfig {
             poll_interval,
             spool_dir,
             concurrency: args.concurrency.max(1),
+            heartbeat: (args.heartbeat_ms > 0 && !args.once)
+                .then(|| Duration::from_millis(args.heartbeat_ms)),
+            preview: (args.preview_ms > 0 && !args.once)
+                .then(|| Duration::from_millis(args.preview_ms)),
             verbose: args.verbose,
             dry_run: args.dry_run,
-            gzip_enabled: !args.no_gzip,
+            once: args.once,
+            session_end,
+            compression,
             upload,
             manifest_state_dir,
             created_at,
             ui,
+            pricing,
+            format: args.format,
+            checkpoints,
+            event_fields,
+            redactor,
+            keyring,
+            event_filter,
+            attachments: args
+                .attachment_bytes
+                .filter(|bytes| *bytes > 0)
+                .map(|min_bytes| AttachmentPolicy { min_bytes }),
         })
     }
 
+    /// Derive the per-session config used when `--dir` attaches a newly discovered file.
+    pub fn for_session_file(&self, path: &Path) -> Result<Self> {
+        let sid = sid_for_session_file(path)?;
+        let mut config = self.clone();
+        config.session_file = path.to_path_buf();
+        config.session_dir = None;
+        config.sid = sid;
+        config.created_at = OffsetDateTime::now_utc();
+        Ok(config)
+    }
+
     pub fn object_prefix(&self) -> String {
         format!("{}/{}", self.root_prefix.trim_end_matches('/'), self.sid)
     }
+
+    /// Warn about `--event-rules` drop rules that can match checkpoint or `token_count`
+    /// events: dropped lines never reach checkpoint or usage tracking. Called once
+    /// tracing is up.
+    pub fn warn_on_dropped_signals(&self) {
+        let Some(filter) = &self.event_filter else {
+            return;
+        };
+        let checkpoints = match &self.checkpoints {
+            Some(rules) => vec![rules.clone()],
+            None => SessionFormat::value_variants()
+                .iter()
+                .filter(|format| **format == self.format || self.format == SessionFormat::Auto)
+                .filter_map(|format| format.adapter())
+                .map(|adapter| adapter.checkpoint_rules())
+                .collect(),
+        };
+        for (rule, what) in filter.dropped_signals(&checkpoints) {
+            tracing::warn!(
+                rule,
+                "event rule {rule} can drop {what}; they will not be seen"
+            );
+        }
+    }
+}
+
+/// Settings for `agent-uploader usage`.
+#[derive(Debug, Clone)]
+pub struct UsageConfig {
+    pub sids: Vec<String>,
+    pub since: Option<OffsetDateTime>,
+    pub checkpoints: bool,
+    pub json: bool,
+    pub pricing: Pricing,
+    pub bucket: String,
+    pub root_prefix: String,
+    /// Supabase base URL and key; without them the local state and spool dirs are read.
+    pub supabase: Option<(String, String)>,
+    pub spool_dir: PathBuf,
+    pub manifest_state_dir: PathBuf,
+    pub keyring: Option<Keyring>,
+}
+
+impl UsageConfig {
+    pub fn from_args(args: UsageArgs) -> Result<Self> {
+        let since = args.since.as_deref().map(parse_since).transpose()?;
+        let pricing = match &args.pricing {
+            Some(path) => Pricing::load(&expand_path(path)?)?,
+            None => Pricing::default(),
+        };
+        let spool_dir = match args.spool_dir {
+            Some(path) => expand_path(&path)?,
+            None => default_spool_dir()?,
+        };
+        let manifest_state_dir = match args.state_dir {
+            Some(path) => expand_path(&path)?,
+            None => spool_dir.join("state"),
+        };
+        let sids = args
+            .sids
+            .iter()
+            .map(|sid| sanitize_sid(sid))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            sids,
+            since,
+            checkpoints: args.checkpoints,
+            json: args.json,
+            pricing,
+            bucket: args.bucket,
+            root_prefix: args.root_prefix,
+            supabase: args.supabase_url.zip(args.supabase_key),
+            spool_dir,
+            manifest_state_dir,
+            keyring: load_keyring(args.encryption_key.as_deref())?,
+        })
+    }
+}
+
+fn load_keyring(path: Option<&Path>) -> Result<Option<Keyring>> {
+    path.map(|path| Keyring::load(&expand_path(path)?))
+        .transpose()
+}
+
+/// Field locations from the pointer flags; any left unset come from the format adapter.
+fn event_fields(timestamp: Vec<String>, event_type: Vec<String>) -> Result<EventFields> {
+    for pointer in timestamp.iter().chain(&event_type) {
+        validate_pointer(pointer)?;
+    }
+    Ok(EventFields {
+        timestamp,
+        event_type,
+    })
+}
+
+/// Accept a bare date (midnight UTC) or a full RFC 3339 timestamp.
+fn parse_since(raw: &str) -> Result<OffsetDateTime> {
+    let raw = raw.trim();
+    if let Ok(ts) = OffsetDateTime::parse(raw, &Rfc3339) {
+        return Ok(ts);
+    }
+    let date = Date::parse(raw, format_description!("[year]-[month]-[day]"))
+        .with_context(|| format!("invalid --since {raw:?}; expected YYYY-MM-DD or RFC 3339"))?;
+    Ok(date.midnight().assume_utc())
 }
 
 fn default_spool_dir() -> Result<PathBuf> {
@@ -361,7 +958,22 @@ impl UploadConfig {
     }
 }
 
-fn derive_sid_from_session_file(path: &Path) -> Option<String> {
+/// The sid a session file is uploaded under in directory mode: the UUID in its name, or
+/// else its sanitized file stem.
+pub fn sid_for_session_file(path: &Path) -> Result<String> {
+    match derive_sid_from_session_file(path) {
+        Some(derived) => Ok(derived),
+        None => {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            sanitize_sid(&stem)
+        }
+    }
+}
+
+pub fn derive_sid_from_session_file(path: &Path) -> Option<String> {
     let stem = path.file_stem()?.to_string_lossy();
     if let Some(uuid) = extract_uuid(stem.as_ref()) {
         return Some(uuid);
@@ -379,10 +991,10 @@ fn extract_uuid(candidate: &str) -> Option<String> {
     }
     for start in (0..=candidate.len().saturating_sub(36)).rev() {
         let end = start + 36;
-        if let Some(slice) = candidate.get(start..end) {
-            if Uuid::parse_str(slice).is_ok() {
-                return Some(slice.to_string());
-            }
+        if let Some(slice) = candidate.get(start..end)
+            && Uuid::parse_str(slice).is_ok()
+        {
+            return Some(slice.to_string());
         }
     }
     None
@@ -407,4 +1019,31 @@ mod tests {
         let derived = derive_sid_from_session_file(&path).expect("uuid expected");
         assert_eq!(derived, "0199b14b-f650-7c52-93bd-b226acca5ff5");
     }
+
+    #[test]
+    fn dir_mode_derives_sid_per_file() {
+        let cli = Cli::try_parse_from([
+            "agent-uploader",
+            "watch",
+            "--dir",
+            "/tmp/sessions",
+            "--dry-run",
+            "--spool-dir",
+            "/tmp/spool",
+        ])
+        .expect("valid args");
+        let Command::Watch(args) = cli.command else {
+            panic!("expected watch command");
+        };
+        let config = WatchConfig::from_args(*args).expect("config");
+        assert!(config.session_dir.is_some());
+
+        let file = PathBuf::from(
+            "/tmp/sessions/2025/10/04/rollout-2025-10-04T15-16-09-0199b14b-f650-7c52-93bd-b226acca5ff5.jsonl",
+        );
+        let session = config.for_session_file(&file).expect("session config");
+        assert_eq!(session.sid, "0199b14b-f650-7c52-93bd-b226acca5ff5");
+        assert_eq!(session.session_file, file);
+        assert!(session.session_dir.is_none());
+    }
 }
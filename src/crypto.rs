@@ -0,0 +1,321 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const ALGORITHM: &str = "aes-256-gcm";
+/// Appended to the names of encrypted segment objects.
+pub const SEALED_SUFFIX: &str = ".enc";
+/// Leads every binary envelope: magic, key id length, key id, nonce, ciphertext.
+const MAGIC: &[u8] = b"AUENC1";
+const NONCE_LEN: usize = 12;
+
+/// Which key sealed an object; recorded in manifests so readers know what to load.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionInfo {
+    pub algorithm: String,
+    pub key_id: String,
+}
+
+/// A JSON object (manifest, checkpoint, session index) whose body is sealed. The key id
+/// stays readable so the object can be matched to a key without decrypting it.
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedJson {
+    encryption: EncryptionInfo,
+    /// Base64 of nonce followed by ciphertext.
+    sealed: String,
+}
+
+/// Keys from a `--encryption-key` file. The first key seals new objects; all of them can
+/// open old ones, so a key can be rotated by prepending its successor.
+#[derive(Clone)]
+pub struct Keyring {
+    keys: Arc<Vec<Key>>,
+}
+
+struct Key {
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<&str> = self.keys.iter().map(|key| key.id.as_str()).collect();
+        f.debug_struct("Keyring").field("keys", &ids).finish()
+    }
+}
+
+impl Keyring {
+    /// Read a key file: one 32-byte key per line, hex encoded. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read encryption key {}", path.display()))?;
+        let mut keys = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bytes = hex::decode(line)
+                .ok()
+                .filter(|bytes| bytes.len() == 32)
+                .with_context(|| {
+                    format!(
+                        "{}:{}: expected a 32-byte key as 64 hex characters",
+                        path.display(),
+                        idx + 1
+                    )
+                })?;
+            keys.push(Key::new(&bytes));
+        }
+        if keys.is_empty() {
+            bail!("no keys in {}", path.display());
+        }
+        Ok(Self {
+            keys: Arc::new(keys),
+        })
+    }
+
+    /// Algorithm and id of the key that seals new objects.
+    pub fn info(&self) -> EncryptionInfo {
+        EncryptionInfo {
+            algorithm: ALGORITHM.to_string(),
+            key_id: self.keys[0].id.clone(),
+        }
+    }
+
+    /// Encrypt a binary object (a segment) into a self-describing envelope, bound to
+    /// the bucket path it is uploaded to.
+    pub fn seal(&self, object_path: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let key = &self.keys[0];
+        let mut out = envelope_header(&key.id);
+        let sealed = key.seal(plaintext, &bound_aad(&out, object_path))?;
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Decrypt an envelope written by [`Keyring::seal`] for `object_path`. An object
+    /// copied or moved to another path fails to open.
+    pub fn open(&self, object_path: &str, envelope: &[u8]) -> Result<Vec<u8>> {
+        let (id, header, sealed) = split_envelope(envelope)?;
+        self.key(id)?.open(sealed, &bound_aad(header, object_path))
+    }
+
+    /// Encrypt a JSON document bound to `object_path`, keeping the result JSON.
+    pub fn seal_json(&self, object_path: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let encryption = self.info();
+        let aad = bound_aad(encryption.key_id.as_bytes(), object_path);
+        let sealed = self.keys[0].seal(plaintext, &aad)?;
+        let wrapper = SealedJson {
+            encryption,
+            sealed: BASE64.encode(sealed),
+        };
+        serde_json::to_vec_pretty(&wrapper).context("failed to serialize sealed object")
+    }
+
+    fn key(&self, id: &str) -> Result<&Key> {
+        self.keys
+            .iter()
+            .find(|key| key.id == id)
+            .with_context(|| format!("no encryption key with id {id}"))
+    }
+}
+
+impl Key {
+    fn new(bytes: &[u8]) -> Self {
+        let digest = Sha256::digest(bytes);
+        Self {
+            id: hex::encode(&digest[..8]),
+            cipher: Aes256Gcm::new_from_slice(bytes).expect("key is 32 bytes"),
+        }
+    }
+
+    /// Nonce followed by ciphertext and tag, with `aad` authenticated alongside.
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("truncated ciphertext");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("decryption failed with key {}", self.id))
+    }
+}
+
+/// Authenticated data for an object: its header (or key id) and its bucket path, with
+/// empty path segments dropped so `a//b` and `/a/b` name the same object.
+fn bound_aad(header: &[u8], object_path: &str) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.push(0);
+    for (idx, part) in object_path
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .enumerate()
+    {
+        if idx > 0 {
+            aad.push(b'/');
+        }
+        aad.extend_from_slice(part.as_bytes());
+    }
+    aad
+}
+
+/// Id of the key an envelope written by [`Keyring::seal`] names, without opening it.
+pub fn envelope_key_id(envelope: &[u8]) -> Result<&str> {
+    split_envelope(envelope).map(|(id, _, _)| id)
+}
+
+/// Key id, header and sealed remainder of an envelope.
+fn split_envelope(envelope: &[u8]) -> Result<(&str, &[u8], &[u8])> {
+    let rest = envelope
+        .strip_prefix(MAGIC)
+        .context("object is not an encrypted envelope")?;
+    let (&id_len, rest) = rest.split_first().context("truncated envelope")?;
+    let id_len = id_len as usize;
+    if rest.len() < id_len {
+        bail!("truncated envelope");
+    }
+    let id = std::str::from_utf8(&rest[..id_len]).context("invalid key id in envelope")?;
+    let header_len = MAGIC.len() + 1 + id_len;
+    Ok((id, &envelope[..header_len], &rest[id_len..]))
+}
+
+fn envelope_header(key_id: &str) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.push(key_id.len() as u8);
+    header.extend_from_slice(key_id.as_bytes());
+    header
+}
+
+/// The stored bytes of the object at `object_path`, decrypted if the name ends in
+/// [`SEALED_SUFFIX`]. Returns the path without the suffix alongside.
+pub fn open_object<'a>(
+    keys: Option<&Keyring>,
+    object_path: &'a str,
+    bytes: Vec<u8>,
+) -> Result<(&'a str, Vec<u8>)> {
+    match object_path.strip_suffix(SEALED_SUFFIX) {
+        Some(inner) => {
+            let keys =
+                keys.with_context(|| format!("{object_path} is encrypted; pass --encryption-key"))?;
+            Ok((inner, keys.open(object_path, &bytes)?))
+        }
+        None => Ok((object_path, bytes)),
+    }
+}
+
+/// The JSON document at `object_path` as written, decrypted first if it was sealed with
+/// [`Keyring::seal_json`]. Plain documents are returned unchanged only without a keyring:
+/// with one, anyone able to write the bucket could otherwise swap in their own.
+pub fn open_json(keys: Option<&Keyring>, object_path: &str, bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let Ok(wrapper) = serde_json::from_slice::<SealedJson>(&bytes) else {
+        if keys.is_some() {
+            bail!("{object_path} is not encrypted, but an encryption key is configured");
+        }
+        return Ok(bytes);
+    };
+    let key_id = &wrapper.encryption.key_id;
+    let keys = keys
+        .with_context(|| format!("object is encrypted with key {key_id}; pass --encryption-key"))?;
+    let sealed = BASE64
+        .decode(&wrapper.sealed)
+        .context("invalid base64 in sealed object")?;
+    keys.key(key_id)?
+        .open(&sealed, &bound_aad(key_id.as_bytes(), object_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring(lines: &str) -> Keyring {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, lines).unwrap();
+        Keyring::load(&path).unwrap()
+    }
+
+    #[test]
+    fn sealed_objects_round_trip_across_rotation() {
+        let old = keyring(&"11".repeat(32));
+        let rotated = keyring(&format!(
+            "# new\n{}\n{}\n",
+            "22".repeat(32),
+            "11".repeat(32)
+        ));
+
+        let segment_path = "sessions/s/segments/a.jsonl.enc";
+        let segment = old.seal(segment_path, b"{\"a\":1}\n").unwrap();
+        assert!(!segment.windows(7).any(|window| window == b"{\"a\":1}"));
+        assert_eq!(
+            rotated
+                .open("/sessions//s/segments/a.jsonl.enc", &segment)
+                .unwrap(),
+            b"{\"a\":1}\n"
+        );
+
+        let manifest_path = "sessions/s/manifest.json";
+        let manifest = rotated.seal_json(manifest_path, br#"{"sid":"s"}"#).unwrap();
+        let wrapper: serde_json::Value = serde_json::from_slice(&manifest).unwrap();
+        assert_eq!(wrapper["encryption"]["key_id"], rotated.info().key_id);
+        assert!(open_json(Some(&old), manifest_path, manifest.clone()).is_err());
+        assert_eq!(
+            open_json(Some(&rotated), manifest_path, manifest).unwrap(),
+            br#"{"sid":"s"}"#
+        );
+        assert_eq!(
+            open_json(None, manifest_path, b"{\"sid\":\"s\"}".to_vec()).unwrap(),
+            b"{\"sid\":\"s\"}"
+        );
+        // With a key, a plaintext document is refused rather than trusted.
+        assert!(open_json(Some(&rotated), manifest_path, b"{\"sid\":\"s\"}".to_vec()).is_err());
+    }
+
+    #[test]
+    fn tampered_envelopes_are_rejected() {
+        let keys = keyring(&"33".repeat(32));
+        let path = "sessions/s/segments/a.jsonl.enc";
+        let mut segment = keys.seal(path, b"line\n").unwrap();
+        // Swapping objects between paths is caught like any other tampering.
+        assert!(
+            keys.open("sessions/t/segments/a.jsonl.enc", &segment)
+                .is_err()
+        );
+        let manifest = keys.seal_json("sessions/s/manifest.json", b"{}").unwrap();
+        assert!(open_json(Some(&keys), "sessions/t/manifest.json", manifest).is_err());
+
+        let last = segment.len() - 1;
+        segment[last] ^= 1;
+        assert!(keys.open(path, &segment).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::checkpoint::{CheckpointRules, Predicate};
+use crate::tail::SessionEvent;
+use crate::util::{load_json_rules, validate_pointer};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+/// One rule from `--event-rules`. It applies to events whose `type` matches (if given)
+/// and for which every predicate in `when` holds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventRule {
+    #[serde(default, rename = "type")]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub when: Vec<Predicate>,
+    #[serde(flatten)]
+    pub action: EventAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum EventAction {
+    /// Leave the event out of the session entirely.
+    Drop,
+    /// Cut strings longer than `max_bytes` under each pointer (the whole event when
+    /// none are given) and append a marker with the number of bytes removed.
+    Truncate {
+        max_bytes: usize,
+        #[serde(default)]
+        pointers: Vec<String>,
+    },
+    /// Remove the values at these pointers.
+    Strip { pointers: Vec<String> },
+}
+
+/// Rules applied to parsed events before they reach the segment writer, in file order.
+/// A dropped event stops there; the others see the event as earlier rules left it.
+#[derive(Debug, Clone)]
+pub struct EventFilter {
+    rules: Arc<Vec<EventRule>>,
+}
+
+impl EventFilter {
+    pub fn new(rules: Vec<EventRule>) -> Self {
+        Self {
+            rules: Arc::new(rules),
+        }
+    }
+
+    /// Load a JSON array of rules.
+    pub fn load(path: &Path) -> Result<Self> {
+        let rules: Vec<EventRule> = load_json_rules(path, "event rules")?;
+        for (idx, rule) in rules.iter().enumerate() {
+            rule.validate()
+                .with_context(|| format!("invalid event rule #{}", idx + 1))?;
+        }
+        Ok(Self::new(rules))
+    }
+
+    /// Drop rules that can swallow events other features read: ones matching a
+    /// checkpoint rule in `checkpoints`, and Codex `token_count` events that usage is
+    /// counted from. Returns each rule's number (from 1) and what it can drop. Only event
+    /// types and `equals`/`exists` predicates are compared, so this errs towards warning.
+    pub fn dropped_signals(&self, checkpoints: &[CheckpointRules]) -> Vec<(usize, String)> {
+        let token_count = [Predicate {
+            pointer: "/payload/type".to_string(),
+            equals: Some(Value::from("token_count")),
+            exists: None,
+            contains: None,
+        }];
+        let mut found = Vec::new();
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if !matches!(rule.action, EventAction::Drop) {
+                continue;
+            }
+            for checkpoint in checkpoints.iter().flat_map(CheckpointRules::rules) {
+                if rule.may_match(checkpoint.event_type.as_deref(), &checkpoint.when) {
+                    found.push((idx + 1, format!("{:?} checkpoint events", checkpoint.name)));
+                }
+            }
+            if rule.may_match(Some("event_msg"), &token_count) {
+                found.push((idx + 1, "token_count events".to_string()));
+            }
+        }
+        found.dedup();
+        found
+    }
+
+    /// The event as it should be stored, or `None` when a rule drops it. Edited JSON
+    /// lines are re-encoded; lines that are not JSON can only be dropped or truncated
+    /// as a whole.
+    pub fn apply(&self, mut event: SessionEvent) -> Option<SessionEvent> {
+        let mut edited = false;
+        for rule in self.rules.iter() {
+            if !rule.matches(&event) {
+                continue;
+            }
+            match (&rule.action, event.json.as_mut()) {
+                (EventAction::Drop, _) => return None,
+                (
+                    EventAction::Truncate {
+                        max_bytes,
+                        pointers,
+                    },
+                    Some(json),
+                ) => {
+                    let before = event.truncated;
+                    if pointers.is_empty() {
+                        truncate_strings(json, *max_bytes, &mut event.truncated);
+                    }
+                    for pointer in pointers {
+                        if let Some(value) = json.pointer_mut(pointer) {
+                            truncate_strings(value, *max_bytes, &mut event.truncated);
+                        }
+                    }
+                    edited |= event.truncated > before;
+                }
+                (
+                    EventAction::Truncate {
+                        max_bytes,
+                        pointers,
+                    },
+                    None,
+                ) => {
+                    if pointers.is_empty() && event.raw.len() > *max_bytes {
+                        let text = String::from_utf8_lossy(&event.raw).into_owned();
+                        event.raw = truncate(&text, *max_bytes).into_bytes();
+                        event.truncated += 1;
+                    }
+                }
+                (EventAction::Strip { pointers }, Some(json)) => {
+                    for pointer in pointers {
+                        edited |= remove_pointer(json, pointer).is_some();
+                    }
+                }
+                (EventAction::Strip { .. }, None) => {}
+            }
+        }
+        if edited && let Some(json) = &event.json {
+            event.raw = serde_json::to_vec(json).unwrap_or(event.raw);
+        }
+        Some(event)
+    }
+}
+
+impl EventRule {
+    fn validate(&self) -> Result<()> {
+        let pointers = match &self.action {
+            EventAction::Drop => &[][..],
+            EventAction::Truncate {
+                max_bytes,
+                pointers,
+            } => {
+                if *max_bytes == 0 {
+                    bail!("max_bytes must be greater than 0");
+                }
+                pointers
+            }
+            EventAction::Strip { pointers } => {
+                if pointers.iter().any(String::is_empty) {
+                    bail!("strip cannot remove the whole event; use the drop action");
+                }
+                pointers
+            }
+        };
+        let predicates = self.when.iter().map(|predicate| &predicate.pointer);
+        for pointer in pointers.iter().chain(predicates) {
+            validate_pointer(pointer)?;
+        }
+        Ok(())
+    }
+
+    /// Whether some event could match both this rule and the given type and predicates.
+    fn may_match(&self, event_type: Option<&str>, when: &[Predicate]) -> bool {
+        if let (Some(ours), Some(theirs)) = (&self.event_type, event_type)
+            && ours != theirs
+        {
+            return false;
+        }
+        !self
+            .when
+            .iter()
+            .any(|predicate| when.iter().any(|other| predicate.contradicts(other)))
+    }
+
+    fn matches(&self, event: &SessionEvent) -> bool {
+        if let Some(expected) = &self.event_type
+            && event.event_type.as_deref() != Some(expected.as_str())
+        {
+            return false;
+        }
+        if self.when.is_empty() {
+            return true;
+        }
+        event
+            .json
+            .as_ref()
+            .is_some_and(|json| self.when.iter().all(|predicate| predicate.holds(json)))
+    }
+}
+
+fn truncate_strings(value: &mut Value, max_bytes: usize, count: &mut u64) {
+    match value {
+        Value::String(text) if text.len() > max_bytes => {
+            *text = truncate(text, max_bytes);
+            *count += 1;
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| truncate_strings(item, max_bytes, count)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|item| truncate_strings(item, max_bytes, count)),
+        _ => {}
+    }
+}
+
+/// The first `max_bytes` of `text` (backing off to a char boundary) and a marker.
+fn truncate(text: &str, max_bytes: usize) -> String {
+    let mut cut = max_bytes.min(text.len());
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}[truncated {} bytes]", &text[..cut], text.len() - cut)
+}
+
+/// Remove and return the value at a JSON pointer.
+fn remove_pointer(value: &mut Value, pointer: &str) -> Option<Value> {
+    let (parent, last) = pointer.rsplit_once('/')?;
+    let key = last.replace("~1", "/").replace("~0", "~");
+    match value.pointer_mut(parent)? {
+        Value::Object(map) => map.remove(&key),
+        Value::Array(items) => {
+            let idx: usize = key.parse().ok()?;
+            (idx < items.len()).then(|| items.remove(idx))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rules_drop_truncate_and_strip() {
+        let rules: Vec<EventRule> = serde_json::from_value(json!([
+            {"action": "drop", "type": "event_msg",
+             "when": [{"pointer": "/payload/type", "equals": "token_count"}]},
+            {"action": "truncate", "type": "response_item", "max_bytes": 2,
+             "pointers": ["/payload/output"]},
+            {"action": "strip", "pointers": ["/payload/encrypted_content"]}
+        ]))
+        .unwrap();
+        let filter = EventFilter::new(rules);
+        let event = |line: &str| SessionEvent::from_line(line.as_bytes().to_vec());
+
+        let tokens = r#"{"type":"event_msg","payload":{"type":"token_count"}}"#;
+        assert!(filter.apply(event(tokens)).is_none());
+
+        let output = r#"{"type":"response_item","payload":{"output":"héllo world","call_id":"c1","encrypted_content":"abc"}}"#;
+        let kept = filter.apply(event(output)).unwrap();
+        assert_eq!(kept.truncated, 1);
+        let json: Value = serde_json::from_slice(&kept.raw).unwrap();
+        assert_eq!(
+            json["payload"],
+            json!({"output": "h[truncated 11 bytes]", "call_id": "c1"})
+        );
+
+        let message = r#"{"type":"event_msg","payload":{"type":"user_message","message":"hi"}}"#;
+        let untouched = filter.apply(event(message)).unwrap();
+        assert_eq!(untouched.raw, message.as_bytes());
+        assert_eq!(untouched.truncated, 0);
+    }
+
+    #[test]
+    fn drop_rules_that_hide_checkpoints_or_usage_are_reported() {
+        let rules: Vec<EventRule> = serde_json::from_value(json!([
+            {"action": "drop", "type": "event_msg",
+             "when": [{"pointer": "/payload/type", "equals": "token_count"}]},
+            {"action": "drop", "type": "event_msg",
+             "when": [{"pointer": "/payload/type", "equals": "agent_reasoning"}]},
+            {"action": "drop", "when": [{"pointer": "/payload/text", "exists": true}]},
+            {"action": "truncate", "max_bytes": 10}
+        ]))
+        .unwrap();
+        let filter = EventFilter::new(rules);
+        assert_eq!(
+            filter.dropped_signals(&[CheckpointRules::codex()]),
+            vec![
+                (1, "token_count events".to_string()),
+                (3, "\"compacted\" checkpoint events".to_string()),
+                (3, "token_count events".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use crate::checkpoint::CheckpointRules;
+use crate::metadata::{GitMetadata, SessionMetadata};
+use crate::rollout::{RolloutItem, SessionMeta};
+use crate::tail::parse_timestamp;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// Non-JSON lines `--format auto` reads before settling on plain text.
+const TEXT_DETECT_LINES: usize = 8;
+
+/// Transcript shape selected with `--format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionFormat {
+    /// Pick from the opening lines of each file.
+    #[default]
+    Auto,
+    /// Codex rollout NDJSON.
+    Codex,
+    /// JSONL of chat messages with a `role` (top level or under `message`).
+    Chat,
+    /// Plain-text log lines, optionally starting with a timestamp and level.
+    Text,
+}
+
+/// Normalizes one agent's transcript lines: where the timestamp and type live, which
+/// events are checkpoints, and what they say about the session. JSON is decoded once by
+/// the caller; `json` is `None` for lines that are not JSON.
+pub trait FormatAdapter: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether a file whose first line is this one looks like this format.
+    fn detect(&self, raw: &[u8], json: Option<&Value>) -> bool;
+
+    fn timestamp(&self, raw: &[u8], json: Option<&Value>) -> Option<OffsetDateTime>;
+
+    fn event_type(&self, raw: &[u8], json: Option<&Value>) -> Option<String>;
+
+    /// Checkpoint rules used unless `--checkpoint-rules` replaces them.
+    fn checkpoint_rules(&self) -> CheckpointRules {
+        CheckpointRules::none()
+    }
+
+    /// Session metadata carried by this event, if any.
+    fn metadata(&self, _event_type: Option<&str>, _json: &Value) -> Option<SessionMetadata> {
+        None
+    }
+}
+
+impl SessionFormat {
+    /// The adapter for an explicit format; `None` for `auto`.
+    pub fn adapter(self) -> Option<Arc<dyn FormatAdapter>> {
+        match self {
+            Self::Auto => None,
+            Self::Codex => Some(Arc::new(CodexFormat)),
+            Self::Chat => Some(Arc::new(ChatFormat)),
+            Self::Text => Some(Arc::new(TextFormat)),
+        }
+    }
+
+    /// The explicit format an adapter implements, for recording in the manifest.
+    pub fn of(adapter: &dyn FormatAdapter) -> Self {
+        match adapter.name() {
+            "chat" => Self::Chat,
+            "text" => Self::Text,
+            _ => Self::Codex,
+        }
+    }
+}
+
+/// Settles `--format auto` from a file's opening lines. The first JSON line decides;
+/// plain text only wins after several lines without JSON, so a stray banner or blank
+/// log line ahead of a rollout does not lock the file to `text`.
+#[derive(Debug, Clone, Default)]
+pub struct FormatDetector {
+    text_lines: usize,
+}
+
+impl FormatDetector {
+    /// The adapter once the format is settled; `None` while more lines are needed.
+    pub fn observe(&mut self, raw: &[u8], json: Option<&Value>) -> Option<Arc<dyn FormatAdapter>> {
+        if json.is_some() {
+            return Some(detect(raw, json));
+        }
+        self.text_lines += 1;
+        (self.text_lines >= TEXT_DETECT_LINES).then(|| Arc::new(TextFormat) as _)
+    }
+}
+
+/// Detect the format from the head of `path`, whatever offset tailing resumes at.
+/// `None` when the file has no complete line yet; a file with only a few lines, none
+/// of them JSON, is text.
+pub fn detect_file(path: &Path) -> Result<Option<Arc<dyn FormatAdapter>>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open session file {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut detector = FormatDetector::default();
+    let mut seen_text = false;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        // A line still being written (no newline yet) may be a JSON prefix.
+        if reader.read_until(b'\n', &mut line)? == 0 || line.pop() != Some(b'\n') {
+            break;
+        }
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let json = serde_json::from_slice::<Value>(&line).ok();
+        if let Some(adapter) = detector.observe(&line, json.as_ref()) {
+            return Ok(Some(adapter));
+        }
+        seen_text = true;
+    }
+    Ok(seen_text.then(|| Arc::new(TextFormat) as _))
+}
+
+/// Pick an adapter from a file's first line, falling back to Codex.
+pub fn detect(raw: &[u8], json: Option<&Value>) -> Arc<dyn FormatAdapter> {
+    let candidates: [Arc<dyn FormatAdapter>; 3] = [
+        Arc::new(CodexFormat),
+        Arc::new(ChatFormat),
+        Arc::new(TextFormat),
+    ];
+    candidates
+        .into_iter()
+        .find(|adapter| adapter.detect(raw, json))
+        .unwrap_or_else(|| Arc::new(CodexFormat))
+}
+
+/// Codex CLI rollouts: `{"timestamp", "type", "payload"}` lines, led by `session_meta`.
+#[derive(Debug, Clone, Copy)]
+pub struct CodexFormat;
+
+const CODEX_TYPES: &[&str] = &[
+    "session_meta",
+    "turn_context",
+    "response_item",
+    "event_msg",
+    "compacted",
+];
+
+impl FormatAdapter for CodexFormat {
+    fn name(&self) -> &'static str {
+        "codex"
+    }
+
+    fn detect(&self, _raw: &[u8], json: Option<&Value>) -> bool {
+        let Some(json) = json else {
+            return false;
+        };
+        match json.get("type").and_then(Value::as_str) {
+            Some(event_type) => CODEX_TYPES.contains(&event_type),
+            // Older rollouts open with an untyped line of session details.
+            None => RolloutItem::from_json(json).session_meta().is_some(),
+        }
+    }
+
+    fn timestamp(&self, _raw: &[u8], json: Option<&Value>) -> Option<OffsetDateTime> {
+        pointer_timestamp(json?, &["/timestamp"])
+    }
+
+    fn event_type(&self, _raw: &[u8], json: Option<&Value>) -> Option<String> {
+        pointer_string(json?, &["/type"])
+    }
+
+    fn checkpoint_rules(&self) -> CheckpointRules {
+        CheckpointRules::codex()
+    }
+
+    /// `session_meta` carries cwd, CLI version, instructions and git details; each
+    /// `turn_context` names the model (which can change mid-session) and cwd. Older
+    /// rollouts put `instructions` and `git` on an untyped first line.
+    fn metadata(&self, event_type: Option<&str>, json: &Value) -> Option<SessionMetadata> {
+        if !matches!(event_type, Some("session_meta" | "turn_context") | None) {
+            return None;
+        }
+        match RolloutItem::from_json(json) {
+            RolloutItem::TurnContext(context) => Some(SessionMetadata {
+                model: context.model,
+                cwd: context.cwd,
+                ..SessionMetadata::default()
+            }),
+            item => item.session_meta().map(codex_session_meta),
+        }
+    }
+}
+
+fn codex_session_meta(meta: SessionMeta) -> SessionMetadata {
+    let git = meta.git.map(|git| GitMetadata {
+        commit: git.commit_hash,
+        branch: git.branch,
+        repository_url: git.repository_url,
+    });
+    SessionMetadata {
+        cwd: meta.cwd,
+        model: meta.model,
+        cli_version: meta.cli_version,
+        originator: meta.originator,
+        instructions: meta.instructions,
+        git: git.filter(|git| git != &GitMetadata::default()),
+        ..SessionMetadata::default()
+    }
+}
+
+/// Chat-message transcripts, one message per line: `{"role", "content", ...}` or the
+/// same nested under `message`, with the sender as the event type unless a `type` is set.
+#[derive(Debug, Clone, Copy)]
+pub struct ChatFormat;
+
+impl FormatAdapter for ChatFormat {
+    fn name(&self) -> &'static str {
+        "chat"
+    }
+
+    fn detect(&self, _raw: &[u8], json: Option<&Value>) -> bool {
+        json.and_then(|json| pointer_string(json, &["/role", "/message/role"]))
+            .is_some()
+    }
+
+    fn timestamp(&self, _raw: &[u8], json: Option<&Value>) -> Option<OffsetDateTime> {
+        pointer_timestamp(json?, &["/timestamp", "/created_at", "/time", "/ts"])
+    }
+
+    fn event_type(&self, _raw: &[u8], json: Option<&Value>) -> Option<String> {
+        pointer_string(json?, &["/type", "/role", "/message/role"])
+    }
+
+    fn metadata(&self, _event_type: Option<&str>, json: &Value) -> Option<SessionMetadata> {
+        let branch = pointer_string(json, &["/git_branch", "/gitBranch"]);
+        let metadata = SessionMetadata {
+            model: pointer_string(json, &["/model", "/message/model"]),
+            cwd: pointer_string(json, &["/cwd"]),
+            cli_version: pointer_string(json, &["/version"]),
+            git: branch.map(|branch| GitMetadata {
+                branch: Some(branch),
+                ..GitMetadata::default()
+            }),
+            ..SessionMetadata::default()
+        };
+        (!metadata.is_empty()).then_some(metadata)
+    }
+}
+
+/// Line-oriented logs such as `2025-10-09T08:53:20Z INFO started`. The leading token
+/// (or two, for `date time`) is the timestamp and a level word near the start is the type.
+#[derive(Debug, Clone, Copy)]
+pub struct TextFormat;
+
+const LOG_LEVELS: &[&str] = &[
+    "trace", "debug", "info", "notice", "warn", "warning", "error", "fatal",
+];
+
+impl FormatAdapter for TextFormat {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn detect(&self, _raw: &[u8], json: Option<&Value>) -> bool {
+        json.is_none()
+    }
+
+    fn timestamp(&self, raw: &[u8], _json: Option<&Value>) -> Option<OffsetDateTime> {
+        let text = String::from_utf8_lossy(raw);
+        let tokens: Vec<&str> = log_tokens(&text).take(2).collect();
+        let first = tokens.first()?;
+        let parse = |candidate: String| parse_timestamp(&Value::String(candidate));
+        parse(first.to_string())
+            .or_else(|| parse(tokens.get(1).map(|second| format!("{first} {second}"))?))
+    }
+
+    fn event_type(&self, raw: &[u8], _json: Option<&Value>) -> Option<String> {
+        let text = String::from_utf8_lossy(raw);
+        let level = log_tokens(&text)
+            .take(4)
+            .map(str::to_ascii_lowercase)
+            .find(|token| LOG_LEVELS.contains(&token.as_str()));
+        Some(level.unwrap_or_else(|| "log".to_string()))
+    }
+}
+
+/// Whitespace-separated tokens with surrounding brackets and a trailing colon removed.
+fn log_tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .map(|token| {
+            token
+                .trim_matches(|c| matches!(c, '[' | ']' | '(' | ')'))
+                .trim_end_matches(':')
+        })
+        .filter(|token| !token.is_empty())
+}
+
+pub fn pointer_timestamp<S: AsRef<str>>(json: &Value, pointers: &[S]) -> Option<OffsetDateTime> {
+    pointers
+        .iter()
+        .find_map(|pointer| json.pointer(pointer.as_ref()).and_then(parse_timestamp))
+}
+
+pub fn pointer_string<S: AsRef<str>>(json: &Value, pointers: &[S]) -> Option<String> {
+    pointers.iter().find_map(|pointer| {
+        json.pointer(pointer.as_ref())
+            .and_then(Value::as_str)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn detects_format_from_first_line() {
+        let codex =
+            json!({"timestamp": "2025-10-09T08:53:20Z", "type": "session_meta", "payload": {}});
+        assert_eq!(detect(b"", Some(&codex)).name(), "codex");
+        let chat = json!({"message": {"role": "user", "content": "hi"}, "timestamp": 1760000000});
+        assert_eq!(detect(b"", Some(&chat)).name(), "chat");
+        assert_eq!(detect(b"2025-10-09 08:53:20 INFO up", None).name(), "text");
+    }
+
+    #[test]
+    fn auto_detection_looks_past_stray_text_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollout.jsonl");
+        std::fs::write(
+            &path,
+            "codex starting\n\n{\"timestamp\":\"2025-10-09T08:53:20Z\",\"type\":\"event_msg\"}\n",
+        )
+        .unwrap();
+        assert_eq!(detect_file(&path).unwrap().unwrap().name(), "codex");
+
+        // A JSON line still being written does not count yet.
+        std::fs::write(&path, "{\"timestamp\":").unwrap();
+        assert!(detect_file(&path).unwrap().is_none());
+
+        std::fs::write(&path, "booting\nready\n").unwrap();
+        assert_eq!(detect_file(&path).unwrap().unwrap().name(), "text");
+
+        let mut detector = FormatDetector::default();
+        for _ in 1..TEXT_DETECT_LINES {
+            assert!(detector.observe(b"INFO up", None).is_none());
+        }
+        assert_eq!(detector.observe(b"INFO up", None).unwrap().name(), "text");
+    }
+
+    #[test]
+    fn text_lines_yield_timestamp_and_level() {
+        let raw = b"[2025-10-09 08:53:20] WARN: disk almost full";
+        let expected = OffsetDateTime::from_unix_timestamp(1_760_000_000).unwrap();
+        assert_eq!(TextFormat.timestamp(raw, None), Some(expected));
+        assert_eq!(TextFormat.event_type(raw, None).as_deref(), Some("warn"));
+        assert_eq!(
+            TextFormat.event_type(b"plain line", None).as_deref(),
+            Some("log")
+        );
+    }
+}
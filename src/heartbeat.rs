@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+pub const HEARTBEAT_FILENAME: &str = "live.json";
+
+/// Liveness record refreshed at `<prefix>/live.json` while a session is being watched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub sid: String,
+    pub host: String,
+    pub pid: u32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    pub active_seq: u32,
+    pub buffered_lines: u64,
+    pub spool_depth: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_upload_error: Option<String>,
+}
+
+impl Heartbeat {
+    pub fn remote_path(prefix: &str) -> String {
+        format!("{}/{}", prefix.trim_end_matches('/'), HEARTBEAT_FILENAME)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).context("failed to serialize heartbeat")
+    }
+}
+
+pub fn local_hostname() -> String {
+    hostname::get()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Login name of the user running the uploader, if the environment says.
+pub fn local_username() -> Option<String> {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok().filter(|value| !value.is_empty()))
+}
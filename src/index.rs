@@ -0,0 +1,274 @@
+use crate::manifest::{Manifest, SessionStatus};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// Folder under the root prefix holding one index object per uploader.
+pub const INDEX_DIR: &str = "_index";
+const INDEX_VERSION: u32 = 1;
+
+/// One row of the session index: enough to list and sort sessions without their manifests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub sid: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    pub lines: u64,
+    pub bytes_uncompressed: u64,
+    pub segments: usize,
+    pub checkpoints: usize,
+    #[serde(default)]
+    pub status: SessionStatus,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub ended_at: Option<OffsetDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+impl From<&Manifest> for SessionSummary {
+    fn from(manifest: &Manifest) -> Self {
+        let metadata = &manifest.metadata;
+        let git = metadata.git.as_ref();
+        Self {
+            sid: manifest.sid.clone(),
+            created_at: manifest.created_at,
+            updated_at: manifest.updated_at,
+            lines: manifest.replay_segments().map(|seg| seg.lines).sum(),
+            bytes_uncompressed: manifest
+                .replay_segments()
+                .map(|seg| seg.bytes_uncompressed)
+                .sum(),
+            segments: manifest.segments.len(),
+            checkpoints: manifest.checkpoints.len(),
+            status: manifest.status,
+            ended_at: manifest.ended_at,
+            host: metadata.hostname.clone(),
+            model: metadata.model.clone(),
+            repo: git.and_then(|git| git.repository_url.clone()),
+            branch: git.and_then(|git| git.branch.clone()),
+            cwd: metadata.cwd.clone(),
+        }
+    }
+}
+
+/// The index object one uploader writes at `<root>/_index/<writer>.json`. Each uploader
+/// only ever rewrites its own object, so concurrent uploaders never clobber each other;
+/// readers merge every object and keep the most recently updated row per sid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionIndex {
+    pub version: u32,
+    pub writer: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub generated_at: OffsetDateTime,
+    pub sessions: Vec<SessionSummary>,
+}
+
+impl SessionIndex {
+    pub fn new(writer: String, rows: impl IntoIterator<Item = SessionSummary>) -> Self {
+        Self {
+            version: INDEX_VERSION,
+            writer,
+            generated_at: OffsetDateTime::now_utc(),
+            sessions: rows.into_iter().collect(),
+        }
+    }
+
+    pub fn remote_dir(root_prefix: &str) -> String {
+        format!("{}/{}", root_prefix.trim_matches('/'), INDEX_DIR)
+    }
+
+    pub fn remote_path(root_prefix: &str, writer: &str) -> String {
+        format!("{}/{}.json", Self::remote_dir(root_prefix), writer)
+    }
+
+    /// Stable per-spool writer id: the host name plus a short hash of the spool path, so
+    /// two uploaders on one machine with separate spools keep separate objects.
+    pub fn writer_id(hostname: &str, spool_dir: &Path) -> String {
+        let host: String = hostname
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let digest = Sha256::digest(spool_dir.to_string_lossy().as_bytes());
+        format!("{}-{}", host, &hex::encode(digest)[..8])
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).context("failed to serialize session index")
+    }
+
+    /// Merge index objects from several writers, keeping the newest row for each sid.
+    pub fn merge(
+        indexes: impl IntoIterator<Item = SessionIndex>,
+    ) -> BTreeMap<String, SessionSummary> {
+        let mut rows: BTreeMap<String, SessionSummary> = BTreeMap::new();
+        for index in indexes {
+            for row in index.sessions {
+                match rows.get(&row.sid) {
+                    Some(existing) if existing.updated_at >= row.updated_at => {}
+                    _ => {
+                        rows.insert(row.sid.clone(), row);
+                    }
+                }
+            }
+        }
+        rows
+    }
+}
+
+/// Uploader-side copy of this writer's index, seeded from the manifest state dir so it
+/// covers every session this spool has produced, not just the ones watched right now.
+#[derive(Debug)]
+pub struct IndexWriter {
+    writer: String,
+    rows: BTreeMap<String, SessionSummary>,
+    dirty: bool,
+}
+
+impl IndexWriter {
+    pub fn load(writer: String, state_dir: &Path) -> Self {
+        let mut rows = BTreeMap::new();
+        if let Ok(entries) = std::fs::read_dir(state_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let manifest = std::fs::read(&path)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<Manifest>(&bytes).ok());
+                match manifest {
+                    Some(manifest) => {
+                        rows.insert(manifest.sid.clone(), SessionSummary::from(&manifest));
+                    }
+                    None => {
+                        tracing::debug!(path = %path.display(), "skipping unreadable manifest for index");
+                    }
+                }
+            }
+        }
+        Self {
+            writer,
+            dirty: !rows.is_empty(),
+            rows,
+        }
+    }
+
+    pub fn writer(&self) -> &str {
+        &self.writer
+    }
+
+    pub fn record(&mut self, manifest: &Manifest) {
+        self.rows
+            .insert(manifest.sid.clone(), SessionSummary::from(manifest));
+        self.dirty = true;
+    }
+
+    /// Ask for the index to be written again, e.g. after queuing it failed.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// The index to upload if anything changed since the last call.
+    pub fn take_dirty(&mut self) -> Option<SessionIndex> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        Some(SessionIndex::new(
+            self.writer.clone(),
+            self.rows.values().cloned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn row(sid: &str, updated_at: OffsetDateTime, lines: u64) -> SessionSummary {
+        let mut manifest: Manifest =
+            serde_json::from_value(serde_json::json!({ "sid": sid })).unwrap();
+        manifest.updated_at = updated_at;
+        SessionSummary {
+            lines,
+            ..SessionSummary::from(&manifest)
+        }
+    }
+
+    #[test]
+    fn merge_keeps_the_newest_row_from_any_writer() {
+        let early = datetime!(2025-10-04 10:00 UTC);
+        let late = datetime!(2025-10-04 11:00 UTC);
+        // Two uploaders both saw s1 (e.g. a session moved between machines); each also
+        // has a session the other never wrote.
+        let laptop = SessionIndex::new("laptop".into(), [row("s1", late, 20), row("s2", early, 5)]);
+        let server = SessionIndex::new("server".into(), [row("s1", early, 10), row("s3", late, 7)]);
+
+        for indexes in [
+            vec![laptop.clone(), server.clone()],
+            vec![server.clone(), laptop.clone()],
+        ] {
+            let merged = SessionIndex::merge(indexes);
+            assert_eq!(merged.keys().collect::<Vec<_>>(), ["s1", "s2", "s3"]);
+            assert_eq!(merged["s1"].lines, 20);
+            assert_eq!(merged["s1"].updated_at, late);
+        }
+    }
+
+    #[test]
+    fn writer_load_seeds_rows_from_state_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest: Manifest = serde_json::from_str(r#"{"sid": "s1"}"#).unwrap();
+        std::fs::write(dir.path().join("s1.json"), manifest.to_bytes().unwrap()).unwrap();
+        std::fs::write(dir.path().join("broken.json"), b"{").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"ignored").unwrap();
+
+        let mut writer = IndexWriter::load("host-1234".into(), dir.path());
+        let index = writer.take_dirty().unwrap();
+        assert_eq!(index.writer, "host-1234");
+        assert_eq!(
+            index
+                .sessions
+                .iter()
+                .map(|row| &row.sid)
+                .collect::<Vec<_>>(),
+            ["s1"]
+        );
+        assert!(writer.take_dirty().is_none());
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(
+            IndexWriter::load("w".into(), empty.path())
+                .take_dirty()
+                .is_none()
+        );
+        assert!(
+            IndexWriter::load("w".into(), &empty.path().join("missing"))
+                .take_dirty()
+                .is_none()
+        );
+    }
+}
@@ -1,13 +1,33 @@
+pub mod attachments;
+pub mod backfill;
+pub mod cache;
+pub mod checkpoint;
+pub mod compression;
 pub mod config;
+pub mod crypto;
+pub mod filter;
+pub mod format;
+pub mod heartbeat;
+pub mod index;
+pub mod live;
 pub mod manifest;
+pub mod metadata;
+pub mod redact;
+pub mod rollout;
 pub mod segment;
 pub mod spool;
+pub mod storage;
 pub mod tail;
+pub mod turns;
 pub mod ui;
 pub mod upload;
+pub mod usage;
 pub mod util;
 pub mod watch;
 
-pub use config::{Cli, Command, HostArgs, ReloadArgs, ReplayArgs, WatchArgs, WatchConfig};
+pub use config::{
+    BackfillArgs, Cli, Command, HostArgs, ReloadArgs, ReplayArgs, UsageArgs, UsageConfig,
+    WatchArgs, WatchConfig,
+};
 
 pub type Result<T> = anyhow::Result<T>;
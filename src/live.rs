@@ -0,0 +1,187 @@
+use crate::tail::SessionEvent;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::broadcast;
+
+const BACKLOG_LINES: usize = 2_000;
+const CHANNEL_CAPACITY: usize = 1_024;
+
+/// A line as it was appended to a session, addressed by the same `(seq, line_idx)`
+/// position the manifest and replay API use, so a client can switch between them.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveLine {
+    pub seq: u32,
+    pub line_idx: u64,
+    pub ts: i64,
+    pub line: Value,
+}
+
+impl LiveLine {
+    pub fn new(seq: u32, line_idx: u64, event: &SessionEvent) -> Self {
+        let line = event
+            .json
+            .clone()
+            .unwrap_or_else(|| Value::String(String::from_utf8_lossy(&event.raw).into_owned()));
+        Self {
+            seq,
+            line_idx,
+            ts: event.unix_ts,
+            line,
+        }
+    }
+
+    pub fn position(&self) -> (u32, u64) {
+        (self.seq, self.line_idx)
+    }
+}
+
+/// In-process fan-out of freshly tailed lines to streaming UI clients. Each session keeps
+/// a bounded backlog so reconnecting clients can resume from their last position. Feeds
+/// exist only while a watcher has the session open.
+#[derive(Debug, Default)]
+pub struct LiveHub {
+    sessions: Mutex<HashMap<String, SessionFeed>>,
+}
+
+#[derive(Debug)]
+struct SessionFeed {
+    sender: broadcast::Sender<LiveLine>,
+    backlog: VecDeque<LiveLine>,
+}
+
+pub struct LiveSubscription {
+    pub backlog: Vec<LiveLine>,
+    pub receiver: broadcast::Receiver<LiveLine>,
+}
+
+impl LiveHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a feed for a session being tailed; an existing feed is kept.
+    pub fn open(&self, sid: &str) {
+        self.sessions
+            .lock()
+            .entry(sid.to_string())
+            .or_insert_with(SessionFeed::new);
+    }
+
+    /// Drop a session's feed and backlog. Subscribers see the end of the stream.
+    pub fn close(&self, sid: &str) {
+        self.sessions.lock().remove(sid);
+    }
+
+    /// Buffer and broadcast a line; lines for sessions that are not open are dropped.
+    pub fn publish(&self, sid: &str, line: LiveLine) {
+        let mut sessions = self.sessions.lock();
+        let Some(feed) = sessions.get_mut(sid) else {
+            return;
+        };
+        if feed.backlog.len() == BACKLOG_LINES {
+            feed.backlog.pop_front();
+        }
+        feed.backlog.push_back(line.clone());
+        // No receivers is the common case when nobody has the dashboard open.
+        let _ = feed.sender.send(line);
+    }
+
+    /// Subscribe to a session, returning buffered lines strictly after `after`. Both happen
+    /// under the same lock as `publish`, so the backlog and the live stream never overlap
+    /// or leave a gap. A session that is not open gets an empty, already ended stream.
+    pub fn subscribe(&self, sid: &str, after: Option<(u32, u64)>) -> LiveSubscription {
+        let sessions = self.sessions.lock();
+        let Some(feed) = sessions.get(sid) else {
+            return LiveSubscription {
+                backlog: Vec::new(),
+                receiver: broadcast::channel(1).1,
+            };
+        };
+        let backlog = feed
+            .backlog
+            .iter()
+            .filter(|line| after.is_none_or(|after| line.position() > after))
+            .cloned()
+            .collect();
+        LiveSubscription {
+            backlog,
+            receiver: feed.sender.subscribe(),
+        }
+    }
+}
+
+impl SessionFeed {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            backlog: VecDeque::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    fn line(seq: u32, line_idx: u64) -> LiveLine {
+        LiveLine {
+            seq,
+            line_idx,
+            ts: 0,
+            line: Value::Null,
+        }
+    }
+
+    #[test]
+    fn subscribers_resume_after_their_cursor() {
+        let hub = LiveHub::new();
+        hub.open("s1");
+        for (seq, line_idx) in [(1, 0), (1, 1), (2, 0)] {
+            hub.publish("s1", line(seq, line_idx));
+        }
+        let positions = |sub: &LiveSubscription| {
+            sub.backlog
+                .iter()
+                .map(LiveLine::position)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(positions(&hub.subscribe("s1", None)).len(), 3);
+        let mut sub = hub.subscribe("s1", Some((1, 0)));
+        assert_eq!(positions(&sub), [(1, 1), (2, 0)]);
+
+        hub.publish("s1", line(2, 1));
+        assert_eq!(sub.receiver.try_recv().unwrap().position(), (2, 1));
+
+        for idx in 0..BACKLOG_LINES as u64 {
+            hub.publish("s1", line(3, idx));
+        }
+        let backlog = hub.subscribe("s1", None).backlog;
+        assert_eq!(backlog.len(), BACKLOG_LINES);
+        assert_eq!(backlog[0].position(), (3, 0));
+    }
+
+    #[test]
+    fn closed_sessions_end_their_streams() {
+        let hub = LiveHub::new();
+        hub.open("s1");
+        hub.publish("s1", line(1, 0));
+        let mut sub = hub.subscribe("s1", None);
+
+        hub.close("s1");
+        hub.publish("s1", line(1, 1));
+        assert!(hub.sessions.lock().is_empty());
+        assert!(matches!(sub.receiver.try_recv(), Err(TryRecvError::Closed)));
+
+        let mut late = hub.subscribe("s1", None);
+        assert!(late.backlog.is_empty());
+        assert!(matches!(
+            late.receiver.try_recv(),
+            Err(TryRecvError::Closed)
+        ));
+        assert!(hub.sessions.lock().is_empty());
+    }
+}
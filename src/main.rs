@@ -1,6 +1,10 @@
 use agent_uploader::Result;
-use agent_uploader::config::{Cli, Command, WatchArgs, WatchConfig};
-use agent_uploader::{ui, watch};
+use agent_uploader::config::{
+    BackfillArgs, Cli, Command, UsageArgs, UsageConfig, WatchArgs, WatchConfig,
+};
+use agent_uploader::live::LiveHub;
+use agent_uploader::usage::UsageTable;
+use agent_uploader::{backfill, ui, usage, watch};
 use clap::Parser;
 use std::sync::Arc;
 
@@ -22,10 +26,12 @@ fn init_tracing(verbose: bool) {
 async fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli.command {
-        Command::Watch(args) => run_watch(args).await,
+        Command::Watch(args) => run_watch(*args).await,
+        Command::Backfill(args) => run_backfill(*args).await,
         Command::Reload(_) => anyhow::bail!("reload subcommand not implemented yet"),
         Command::Replay(_) => anyhow::bail!("replay subcommand not implemented yet"),
         Command::Host(_) => anyhow::bail!("host subcommand not implemented yet"),
+        Command::Usage(args) => run_usage(args).await,
         Command::Version => {
             println!("agent-uploader {}", env!("CARGO_PKG_VERSION"));
             Ok(())
@@ -36,14 +42,24 @@ async fn main() -> Result<()> {
 async fn run_watch(args: WatchArgs) -> Result<()> {
     let config = Arc::new(WatchConfig::from_args(args)?);
     init_tracing(config.verbose);
-    tracing::info!(
-        sid = tracing::field::display(&config.sid),
-        "starting agent-uploader watch"
-    );
+    config.warn_on_dropped_signals();
+    match &config.session_dir {
+        Some(dir) => tracing::info!(
+            dir = tracing::field::display(dir.root.display()),
+            "starting agent-uploader watch"
+        ),
+        None => tracing::info!(
+            sid = tracing::field::display(&config.sid),
+            "starting agent-uploader watch"
+        ),
+    }
 
-    let ui_handle = ui::spawn(config.clone()).await?;
+    let live = Arc::new(LiveHub::new());
+    let ui_handle = ui::spawn(config.clone(), live.clone()).await?;
 
-    let result = watch::run(config.clone()).await;
+    // Only keep a live backlog when a UI is actually serving it.
+    let live = ui_handle.as_ref().map(|_| live);
+    let result = watch::run(config.clone(), live).await;
 
     if let Some(handle) = ui_handle {
         handle.shutdown().await;
@@ -60,3 +76,40 @@ async fn run_watch(args: WatchArgs) -> Result<()> {
         }
     }
 }
+
+async fn run_backfill(args: BackfillArgs) -> Result<()> {
+    let force = args.force;
+    let config = Arc::new(WatchConfig::from_backfill_args(args)?);
+    init_tracing(config.verbose);
+    config.warn_on_dropped_signals();
+
+    let summary = backfill::run(config, force).await?;
+    println!("{summary}");
+    if summary.failed > 0 || summary.pending > 0 {
+        anyhow::bail!(
+            "backfill incomplete: {} files failed, {} items still queued",
+            summary.failed,
+            summary.pending
+        );
+    }
+    Ok(())
+}
+
+async fn run_usage(args: UsageArgs) -> Result<()> {
+    let config = UsageConfig::from_args(args)?;
+    init_tracing(false);
+
+    let reports = usage::run(&config).await?;
+    if config.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        print!(
+            "{}",
+            UsageTable {
+                reports: &reports,
+                checkpoints: config.checkpoints,
+            }
+        );
+    }
+    Ok(())
+}
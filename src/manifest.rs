@@ -1,7 +1,13 @@
+use crate::attachments::AttachmentEntry;
 use crate::config::WatchConfig;
+use crate::crypto::{EncryptionInfo, Keyring};
+use crate::format::SessionFormat;
+use crate::metadata::SessionMetadata;
+use crate::usage::{TokenUsage, merge_usage, total_usage};
 use crate::util::ensure_dir;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use time::OffsetDateTime;
 
@@ -22,6 +28,78 @@ pub struct Manifest {
     pub checkpoints: Vec<ManifestCheckpoint>,
     #[serde(default)]
     pub active_seq: u32,
+    /// Bytes of the session file written to segments so far, closed or active. Tailing
+    /// resumes here when the uploader restarts.
+    #[serde(default)]
+    pub source_offset: u64,
+    /// Transcript format the session file was read as, so a restart that resumes mid-file
+    /// keeps it instead of detecting again from whichever line comes next.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<SessionFormat>,
+    /// In-progress snapshot of the active segment; dropped once that segment rotates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<SegmentEntry>,
+    #[serde(default)]
+    pub status: SessionStatus,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub ended_at: Option<OffsetDateTime>,
+    #[serde(default, skip_serializing_if = "SessionMetadata::is_empty")]
+    pub metadata: SessionMetadata,
+    /// Running totals over the closed segments, updated at every rotation.
+    #[serde(default)]
+    pub stats: SessionStats,
+    /// Objects holding strings moved out of lines, keyed by SHA-256.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attachments: BTreeMap<String, AttachmentEntry>,
+    /// Key that seals this session's new uploads; the uploaded manifest repeats it in
+    /// clear. Segments record their own `key_id`, so earlier ones stay attributable after
+    /// a rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionInfo>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub lines: u64,
+    pub bytes_uncompressed: u64,
+    pub bytes_compressed: u64,
+    /// Event timestamps (unix seconds) of the first and last line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_ts: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_ts: Option<i64>,
+    pub duration_secs: i64,
+    /// Lines per event `type`; lines without one are only counted in `lines`.
+    #[serde(default)]
+    pub event_types: BTreeMap<String, u64>,
+    pub invalid_json: u64,
+    /// Lines without a readable timestamp, which took the previous line's.
+    #[serde(default)]
+    pub inferred_timestamps: u64,
+    /// Events left out by `--event-rules`; not counted in `lines`.
+    #[serde(default)]
+    pub dropped: u64,
+    /// String fields shortened by `--event-rules`.
+    #[serde(default)]
+    pub truncated: u64,
+    /// Token usage keyed by model.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tokens: BTreeMap<String, TokenUsage>,
+    /// Secrets replaced before upload, per detector.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub redactions: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    #[default]
+    Live,
+    Complete,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -32,9 +110,29 @@ pub struct SegmentEntry {
     pub last_ts: i64,
     pub lines: u64,
     pub bytes_uncompressed: u64,
+    /// Size as stored, whatever the codec (the name predates `--compression`).
     pub bytes_gzip: u64,
+    /// Codec the segment was compressed with (`gzip` or `zstd`); unset when stored raw.
+    /// Entries written before `--compression` rely on the `.gz` extension instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub checksum: Option<String>,
+    /// Id of the key that sealed this segment; unset when it is stored in clear.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// Token usage reported by events in this segment, keyed by model.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tokens: BTreeMap<String, TokenUsage>,
+    /// Secrets replaced in this segment, per detector.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub redactions: BTreeMap<String, u64>,
+    /// Events in this segment's range dropped by `--event-rules`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dropped: u64,
+    /// Strings shortened by `--event-rules` in this segment.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub truncated: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -59,6 +157,13 @@ pub struct SegmentStats {
     pub bytes_uncompressed: u64,
     pub bytes_gzip: u64,
     pub checksum: Option<String>,
+    pub event_types: BTreeMap<String, u64>,
+    pub invalid_json: u64,
+    pub inferred_timestamps: u64,
+    pub dropped: u64,
+    pub truncated: u64,
+    pub tokens: BTreeMap<String, TokenUsage>,
+    pub redactions: BTreeMap<String, u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +181,15 @@ impl Manifest {
             segments: Vec::new(),
             checkpoints: Vec::new(),
             active_seq: 1,
+            source_offset: 0,
+            format: None,
+            preview: None,
+            status: SessionStatus::Live,
+            ended_at: None,
+            metadata: SessionMetadata::default(),
+            stats: SessionStats::default(),
+            attachments: BTreeMap::new(),
+            encryption: config.keyring.as_ref().map(Keyring::info),
         }
     }
 
@@ -106,10 +220,53 @@ impl Manifest {
         } else {
             manifest.active_seq = manifest.segments.last().map(|seg| seg.seq + 1).unwrap_or(1);
         }
+        if manifest.stats.lines == 0 && !manifest.segments.is_empty() {
+            // Written before stats existed: recover what the segment entries still hold.
+            manifest.stats = SessionStats::from_segments(&manifest.segments);
+        }
+        // Objects from here on are sealed (or not) with the current key.
+        let encryption = config.keyring.as_ref().map(Keyring::info);
+        if let Some(previous) = &manifest.encryption
+            && encryption.as_ref() != Some(previous)
+        {
+            tracing::warn!(
+                session = %manifest.sid,
+                previous = %previous.key_id,
+                current = encryption.as_ref().map_or("none", |info| info.key_id.as_str()),
+                "session resumes with a different encryption key; earlier segments keep theirs"
+            );
+        }
+        manifest.encryption = encryption;
         Ok(manifest)
     }
 
-    pub fn add_segment(&mut self, segment: SegmentEntry) {
+    pub fn total_lines(&self) -> u64 {
+        self.segments.iter().map(|seg| seg.lines).sum()
+    }
+
+    pub fn total_bytes_uncompressed(&self) -> u64 {
+        self.segments.iter().map(|seg| seg.bytes_uncompressed).sum()
+    }
+
+    /// Closed segments followed by the preview of the active one, in replay order.
+    pub fn replay_segments(&self) -> impl Iterator<Item = &SegmentEntry> {
+        self.segments.iter().chain(self.preview.iter())
+    }
+
+    pub fn set_preview(&mut self, preview: SegmentEntry) {
+        self.preview = Some(preview);
+        self.touch_updated();
+    }
+
+    pub fn add_segment(&mut self, segment: SegmentEntry, stats: &SegmentStats) {
+        self.stats.record(stats);
+        if self
+            .preview
+            .as_ref()
+            .is_some_and(|preview| preview.seq <= segment.seq)
+        {
+            self.preview = None;
+        }
         self.active_seq = segment.seq + 1;
         self.segments.push(segment);
         self.touch_updated();
@@ -120,6 +277,26 @@ impl Manifest {
         self.touch_updated();
     }
 
+    pub fn add_attachment(&mut self, hash: String, entry: AttachmentEntry) {
+        self.attachments.insert(hash, entry);
+        self.touch_updated();
+    }
+
+    pub fn mark_complete(&mut self, ended_at: OffsetDateTime) {
+        self.status = SessionStatus::Complete;
+        self.ended_at = Some(ended_at);
+        self.touch_updated();
+    }
+
+    /// A completed session that receives new data is live again.
+    pub fn reopen(&mut self) {
+        if self.status == SessionStatus::Complete {
+            self.status = SessionStatus::Live;
+            self.ended_at = None;
+            self.touch_updated();
+        }
+    }
+
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
         serde_json::to_writer_pretty(&mut buf, self).context("failed to serialize manifest")?;
@@ -131,6 +308,67 @@ impl Manifest {
     }
 }
 
+impl SessionStats {
+    /// Totals recoverable from segment entries alone; the type histogram and invalid
+    /// line count start empty.
+    pub fn from_segments(segments: &[SegmentEntry]) -> Self {
+        let mut stats = Self::default();
+        for segment in segments {
+            merge_usage(&mut stats.tokens, &segment.tokens);
+            merge_counts(&mut stats.redactions, &segment.redactions);
+            stats.record_totals(
+                segment.lines,
+                segment.bytes_uncompressed,
+                segment.bytes_gzip,
+                segment.first_ts,
+                segment.last_ts,
+            );
+        }
+        stats
+    }
+
+    pub fn record(&mut self, segment: &SegmentStats) {
+        self.record_totals(
+            segment.lines,
+            segment.bytes_uncompressed,
+            segment.bytes_gzip,
+            segment.first_ts,
+            segment.last_ts,
+        );
+        merge_counts(&mut self.event_types, &segment.event_types);
+        self.invalid_json += segment.invalid_json;
+        self.inferred_timestamps += segment.inferred_timestamps;
+        self.dropped += segment.dropped;
+        self.truncated += segment.truncated;
+        merge_usage(&mut self.tokens, &segment.tokens);
+        merge_counts(&mut self.redactions, &segment.redactions);
+    }
+
+    pub fn total_tokens(&self) -> TokenUsage {
+        total_usage(self.tokens.values())
+    }
+
+    fn record_totals(
+        &mut self,
+        lines: u64,
+        bytes_uncompressed: u64,
+        bytes_compressed: u64,
+        first_ts: i64,
+        last_ts: i64,
+    ) {
+        self.lines += lines;
+        self.bytes_uncompressed += bytes_uncompressed;
+        self.bytes_compressed += bytes_compressed;
+        if lines > 0 {
+            self.first_ts = Some(self.first_ts.map_or(first_ts, |ts| ts.min(first_ts)));
+            self.last_ts = Some(self.last_ts.map_or(last_ts, |ts| ts.max(last_ts)));
+        }
+        if let (Some(first), Some(last)) = (self.first_ts, self.last_ts) {
+            self.duration_secs = last - first;
+        }
+    }
+}
+
 impl SegmentEntry {
     pub fn new(seq: u32, path: String, stats: SegmentStats) -> Self {
         Self {
@@ -141,11 +379,27 @@ impl SegmentEntry {
             lines: stats.lines,
             bytes_uncompressed: stats.bytes_uncompressed,
             bytes_gzip: stats.bytes_gzip,
+            compression: None,
             checksum: stats.checksum,
+            key_id: None,
+            tokens: stats.tokens,
+            redactions: stats.redactions,
+            dropped: stats.dropped,
+            truncated: stats.truncated,
         }
     }
 }
 
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+fn merge_counts(into: &mut BTreeMap<String, u64>, other: &BTreeMap<String, u64>) {
+    for (key, count) in other {
+        *into.entry(key.clone()).or_default() += count;
+    }
+}
+
 fn default_created_at() -> OffsetDateTime {
     OffsetDateTime::now_utc()
 }
@@ -184,3 +438,28 @@ impl ManifestStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn completed_sessions_reopen_on_new_data() {
+        let mut manifest: Manifest = serde_json::from_str(r#"{"sid": "s1"}"#).unwrap();
+        assert_eq!(manifest.status, SessionStatus::Live);
+
+        let ended_at = datetime!(2025-10-04 15:20 UTC);
+        manifest.mark_complete(ended_at);
+        let stored: Manifest = serde_json::from_slice(&manifest.to_bytes().unwrap()).unwrap();
+        assert_eq!(stored.status, SessionStatus::Complete);
+        assert_eq!(stored.ended_at, Some(ended_at));
+
+        manifest.reopen();
+        assert_eq!(manifest.status, SessionStatus::Live);
+        assert_eq!(manifest.ended_at, None);
+        let updated_at = manifest.updated_at;
+        manifest.reopen();
+        assert_eq!(manifest.updated_at, updated_at);
+    }
+}
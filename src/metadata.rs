@@ -0,0 +1,85 @@
+use crate::tail::SessionEvent;
+use serde::{Deserialize, Serialize};
+
+/// Instructions can run to many kilobytes; the manifest keeps a prefix for display.
+const MAX_INSTRUCTIONS_CHARS: usize = 4096;
+
+/// Descriptive fields about a session, collected from its leading events plus the machine
+/// doing the upload. Everything is optional because older rollouts carry less of it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cli_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub originator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_user: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GitMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<String>,
+}
+
+impl SessionMetadata {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Record the uploading machine unless the manifest already names one.
+    pub fn set_origin(&mut self, hostname: String, os_user: Option<String>) {
+        self.hostname.get_or_insert(hostname);
+        if self.os_user.is_none() {
+            self.os_user = os_user;
+        }
+    }
+
+    /// Pick up the metadata the format adapter read from an event; returns whether
+    /// anything changed. The model can be switched mid-session, so the latest one wins;
+    /// every other field keeps the first value seen.
+    pub fn observe(&mut self, event: &SessionEvent) -> bool {
+        let Some(seen) = &event.metadata else {
+            return false;
+        };
+        let before = self.clone();
+        if let Some(model) = &seen.model {
+            self.model = Some(model.clone());
+        }
+        fill(&mut self.cwd, seen.cwd.clone());
+        fill(&mut self.cli_version, seen.cli_version.clone());
+        fill(&mut self.originator, seen.originator.clone());
+        fill(
+            &mut self.instructions,
+            seen.instructions
+                .as_ref()
+                .map(|text| text.chars().take(MAX_INSTRUCTIONS_CHARS).collect()),
+        );
+        if self.git.is_none() {
+            self.git = seen.git.clone();
+        }
+        fill(&mut self.hostname, seen.hostname.clone());
+        fill(&mut self.os_user, seen.os_user.clone());
+        *self != before
+    }
+}
+
+fn fill(slot: &mut Option<String>, value: Option<String>) {
+    if slot.is_none() {
+        *slot = value;
+    }
+}
@@ -0,0 +1,367 @@
+use crate::usage::TokenUsage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One line of a Codex rollout file: `{"timestamp", "type", "payload"}`. Lines whose
+/// type is unknown, or whose payload does not have the expected shape, are kept whole in
+/// [`RolloutItem::Unknown`] so nothing is lost when Codex adds fields or item kinds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RolloutLine {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(flatten)]
+    pub item: RolloutItem,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum RolloutItem {
+    SessionMeta(SessionMeta),
+    TurnContext(TurnContext),
+    ResponseItem(ResponseItem),
+    EventMsg(EventMsg),
+    Compacted(Compacted),
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+impl RolloutItem {
+    /// Type a decoded line, falling back to `Unknown` instead of failing. A bare
+    /// `{"type":"compacted"}` without a payload still counts as a compaction.
+    pub fn from_json(json: &Value) -> Self {
+        match Self::deserialize(json) {
+            Ok(Self::Unknown(_)) | Err(_)
+                if json.get("type").and_then(Value::as_str) == Some("compacted")
+                    && json.get("payload").is_none() =>
+            {
+                Self::Compacted(Compacted::default())
+            }
+            Ok(item) => item,
+            Err(_) => Self::Unknown(json.clone()),
+        }
+    }
+
+    /// The session details of a `session_meta` line, or of the untyped first line
+    /// (`instructions` and `git` at the top level) older rollouts start with.
+    pub fn session_meta(&self) -> Option<SessionMeta> {
+        match self {
+            Self::SessionMeta(meta) => Some(meta.clone()),
+            Self::Unknown(Value::Object(map))
+                if !map.contains_key("type")
+                    && (map.contains_key("instructions") || map.contains_key("git")) =>
+            {
+                SessionMeta::deserialize(&Value::Object(map.clone())).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub originator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cli_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitInfo>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitInfo {
+    #[serde(alias = "commit", skip_serializing_if = "Option::is_none")]
+    pub commit_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<String>,
+}
+
+/// Written at the start of each turn; the model can change between turns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TurnContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval_policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox_policy: Option<Value>,
+}
+
+/// An item of the model conversation: messages, reasoning and tool calls with their
+/// outputs, matched up by `call_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseItem {
+    Message {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        role: String,
+        #[serde(default)]
+        content: Vec<ContentItem>,
+    },
+    Reasoning {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default)]
+        summary: Vec<ReasoningPart>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<Vec<ReasoningPart>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encrypted_content: Option<String>,
+    },
+    FunctionCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        name: String,
+        /// JSON-encoded arguments, as the model produced them.
+        arguments: String,
+        call_id: String,
+    },
+    FunctionCallOutput {
+        call_id: String,
+        output: FunctionOutput,
+    },
+    CustomToolCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        call_id: String,
+        name: String,
+        input: String,
+    },
+    CustomToolCallOutput {
+        call_id: String,
+        output: String,
+    },
+    LocalShellCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        call_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+        action: Value,
+    },
+    WebSearchCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        action: Option<Value>,
+    },
+    #[serde(untagged)]
+    Other(Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentItem {
+    InputText {
+        text: String,
+    },
+    OutputText {
+        text: String,
+    },
+    InputImage {
+        image_url: String,
+    },
+    #[serde(untagged)]
+    Other(Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReasoningPart {
+    SummaryText {
+        text: String,
+    },
+    ReasoningText {
+        text: String,
+    },
+    #[serde(untagged)]
+    Other(Value),
+}
+
+/// Tool output is a plain string in current rollouts and `{content, success}` in older ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FunctionOutput {
+    Text(String),
+    Structured {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        success: Option<bool>,
+    },
+}
+
+impl FunctionOutput {
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Text(text) | Self::Structured { content: text, .. } => text,
+        }
+    }
+
+    /// `None` when the output does not say.
+    pub fn success(&self) -> Option<bool> {
+        match self {
+            Self::Text(_) => None,
+            Self::Structured { success, .. } => *success,
+        }
+    }
+}
+
+impl ContentItem {
+    /// The text of a text part.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::InputText { text } | Self::OutputText { text } => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl ReasoningPart {
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::SummaryText { text } | Self::ReasoningText { text } => Some(text),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+/// Events the CLI reports alongside the conversation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventMsg {
+    TokenCount(TokenCount),
+    UserMessage {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+    },
+    AgentMessage {
+        message: String,
+    },
+    AgentReasoning {
+        text: String,
+    },
+    TaskStarted {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model_context_window: Option<u64>,
+    },
+    TaskComplete {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_agent_message: Option<String>,
+    },
+    TurnAborted {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    #[serde(untagged)]
+    Other(Value),
+}
+
+/// Token usage report. Current CLIs nest readings under `info`; older ones put the
+/// fields of a single turn at the top level, collected in `flat`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenCount {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<TokenCountInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<Value>,
+    #[serde(flatten)]
+    pub flat: TokenUsage,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenCountInfo {
+    /// Running total for the session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_token_usage: Option<TokenUsage>,
+    /// Usage of the latest turn.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_token_usage: Option<TokenUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_context_window: Option<u64>,
+}
+
+/// History compaction; the payload holds the summary that replaced earlier turns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Compacted {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn typed_items_and_unknown_fallback() {
+        let call = json!({"timestamp": "2025-10-09T08:53:20Z", "type": "response_item", "payload": {
+            "type": "function_call", "name": "shell", "arguments": "{}", "call_id": "c1"
+        }});
+        let line: RolloutLine = serde_json::from_value(call.clone()).unwrap();
+        assert_eq!(line.timestamp.as_deref(), Some("2025-10-09T08:53:20Z"));
+        assert!(matches!(
+            &line.item,
+            RolloutItem::ResponseItem(ResponseItem::FunctionCall { name, .. }) if name == "shell"
+        ));
+        assert_eq!(serde_json::to_value(&line).unwrap(), call);
+
+        let count = json!({"type": "event_msg", "payload": {"type": "token_count",
+            "info": {"total_token_usage": {"input_tokens": 5, "total_tokens": 7}}}});
+        let RolloutItem::EventMsg(EventMsg::TokenCount(count)) = RolloutItem::from_json(&count)
+        else {
+            panic!("expected a token count");
+        };
+        assert_eq!(
+            count.info.unwrap().total_token_usage.unwrap().total_tokens,
+            7
+        );
+
+        let bare = json!({"type": "compacted"});
+        assert_eq!(
+            RolloutItem::from_json(&bare),
+            RolloutItem::Compacted(Compacted::default())
+        );
+
+        let future = json!({"type": "ghost_snapshot", "payload": {"id": 1}});
+        assert_eq!(
+            RolloutItem::from_json(&future),
+            RolloutItem::Unknown(future.clone())
+        );
+        let odd = json!({"type": "response_item", "payload": {"type": "future_item"}});
+        assert!(matches!(
+            RolloutItem::from_json(&odd),
+            RolloutItem::ResponseItem(ResponseItem::Other(_))
+        ));
+    }
+
+    #[test]
+    fn legacy_first_line_reads_as_session_meta() {
+        let legacy = json!({"id": "s1", "instructions": "be brief", "git": {"commit": "abc"}});
+        let meta = RolloutItem::from_json(&legacy).session_meta().unwrap();
+        assert_eq!(meta.instructions.as_deref(), Some("be brief"));
+        assert_eq!(meta.git.unwrap().commit_hash.as_deref(), Some("abc"));
+    }
+}
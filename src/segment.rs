@@ -1,12 +1,16 @@
+use crate::compression::Compression;
 use crate::config::{RotatePolicy, WatchConfig};
+use crate::crypto::{Keyring, SEALED_SUFFIX};
 use crate::manifest::{ManifestCheckpoint, SegmentEntry, SegmentStats};
 use crate::spool::SpoolLayout;
 use crate::tail::{CheckpointTrigger, SessionEvent};
+use crate::usage::{TokenUsage, UsageTracker};
 use crate::util::ensure_dir;
 use anyhow::{Context, Result};
-use async_compression::tokio::write::GzipEncoder;
 use serde::Serialize;
 use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
@@ -16,10 +20,11 @@ use time::OffsetDateTime;
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use tokio::fs::{self, File, OpenOptions};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::time::Instant;
 
 pub const SEGMENT_PREFIX: &str = "session";
+pub const PREVIEW_STEM: &str = "preview";
 const CHECKPOINT_ID_FORMAT: &[FormatItem<'static>] =
     format_description!("[year]-[month]-[day]T[hour]-[minute]-[second]Z");
 
@@ -50,8 +55,15 @@ pub struct SegmentWriter {
     bytes: u64,
     first_ts: Option<i64>,
     last_ts: Option<i64>,
-    pending_checkpoint: Option<PendingCheckpoint>,
-    gzip_enabled: bool,
+    event_types: BTreeMap<String, u64>,
+    invalid_json: u64,
+    inferred_timestamps: u64,
+    dropped: u64,
+    truncated: u64,
+    redactions: BTreeMap<String, u64>,
+    usage: UsageTracker,
+    pending_checkpoints: Vec<PendingCheckpoint>,
+    compression: Compression,
 }
 
 #[derive(Debug, Clone)]
@@ -59,12 +71,23 @@ pub struct SegmentClosed {
     pub entry: SegmentEntry,
     pub stats: SegmentStats,
     pub files: SegmentFileSet,
-    pub checkpoint: Option<PendingCheckpoint>,
+    /// Checkpoints that fired in this segment, in order.
+    pub checkpoints: Vec<PendingCheckpoint>,
     pub upload_local_path: PathBuf,
     pub upload_remote_path: String,
     pub content_encoding: Option<String>,
 }
 
+/// Snapshot of the active segment uploaded to a fixed object so viewers can follow a
+/// session before the segment rotates.
+#[derive(Debug, Clone)]
+pub struct SegmentPreview {
+    pub entry: SegmentEntry,
+    pub local_path: PathBuf,
+    pub remote_path: String,
+    pub content_encoding: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PendingCheckpoint {
     pub id: String,
@@ -133,7 +156,7 @@ impl SegmentWriter {
     ) -> Result<Self> {
         spool.ensure()?;
         let prefix = config.object_prefix();
-        let fileset = SegmentFileSet::new(&spool, &prefix, starting_seq)?;
+        let fileset = SegmentFileSet::new(&spool, &prefix, starting_seq, config.compression)?;
         let file = open_segment_file(&fileset.active_path).await?;
         let policy = RotatePolicy {
             max_bytes: config.rotate.max_bytes,
@@ -144,7 +167,7 @@ impl SegmentWriter {
             .max_wall
             .try_into()
             .unwrap_or(Duration::from_secs(600));
-        let gzip_enabled = config.gzip_enabled;
+        let compression = config.compression;
         Ok(Self {
             config: config.clone(),
             spool,
@@ -158,8 +181,15 @@ impl SegmentWriter {
             bytes: 0,
             first_ts: None,
             last_ts: None,
-            pending_checkpoint: None,
-            gzip_enabled,
+            event_types: BTreeMap::new(),
+            invalid_json: 0,
+            inferred_timestamps: 0,
+            dropped: 0,
+            truncated: 0,
+            redactions: BTreeMap::new(),
+            usage: UsageTracker::default(),
+            pending_checkpoints: Vec::new(),
+            compression,
         })
     }
 
@@ -172,16 +202,29 @@ impl SegmentWriter {
             self.first_ts = Some(ts);
         }
         self.last_ts = Some(ts);
+        if let Some(event_type) = &event.event_type {
+            *self.event_types.entry(event_type.clone()).or_default() += 1;
+        }
+        if event.json.is_none() {
+            self.invalid_json += 1;
+        }
+        if event.timestamp_inferred {
+            self.inferred_timestamps += 1;
+        }
+        self.truncated += event.truncated;
+        for (detector, count) in &event.redactions {
+            *self.redactions.entry(detector.clone()).or_default() += count;
+        }
+        self.usage.observe(event);
 
+        let mut rotate = self.should_rotate();
         if let Some(trigger) = event.checkpoint.as_ref() {
             let pending = self.build_pending_checkpoint(event, trigger)?;
-            self.pending_checkpoint = Some(pending);
-            let closed = self.rotate().await?;
-            self.start_next_segment().await?;
-            return Ok(Some(closed));
+            self.pending_checkpoints.push(pending);
+            rotate |= trigger.rotate;
         }
 
-        if self.should_rotate() {
+        if rotate {
             let closed = self.rotate().await?;
             self.start_next_segment().await?;
             return Ok(Some(closed));
@@ -190,6 +233,11 @@ impl SegmentWriter {
         Ok(None)
     }
 
+    /// Count an event that `--event-rules` kept out of the segment.
+    pub fn record_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
     pub async fn force_rotate(&mut self) -> Result<Option<SegmentClosed>> {
         if self.lines == 0 {
             return Ok(None);
@@ -199,8 +247,23 @@ impl SegmentWriter {
         Ok(Some(closed))
     }
 
-    pub fn gzip_enabled(&self) -> bool {
-        self.gzip_enabled
+    /// Carry the session's model and token total over from its manifest, so usage in
+    /// the next segment is measured from where the closed segments left off.
+    pub fn resume_usage(&mut self, model: Option<String>, total: TokenUsage) {
+        self.usage.resume(model, total);
+    }
+
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// Lines written to the active segment since it was opened.
+    pub fn buffered_lines(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
     }
 
     fn should_rotate(&self) -> bool {
@@ -238,61 +301,158 @@ impl SegmentWriter {
         drop(file);
 
         let fileset = self.fileset.clone();
-        let (bytes_gzip, upload_local_path, upload_remote_path, manifest_path, content_encoding) =
-            if self.gzip_enabled {
-                let gzip_bytes = gzip_file(&fileset.active_path, &fileset.compressed_path).await?;
-                if !self.config.dry_run {
-                    let _ = fs::remove_file(&fileset.active_path).await;
-                }
-                (
-                    gzip_bytes,
-                    fileset.compressed_path.clone(),
-                    fileset.remote_compressed.clone(),
-                    fileset.manifest_path.clone(),
-                    Some("gzip".to_string()),
-                )
-            } else {
-                move_to_queue(&fileset.active_path, &fileset.queued_raw_path).await?;
-                (
-                    self.bytes,
-                    fileset.queued_raw_path.clone(),
-                    fileset.remote_active.clone(),
-                    format!("segments/{}", fileset.active_name),
-                    None,
-                )
-            };
-        let checkpoint = self.pending_checkpoint.take();
-        let stats = SegmentStats {
-            first_ts: self.first_ts.unwrap_or(0),
-            last_ts: self.last_ts.unwrap_or(self.first_ts.unwrap_or(0)),
-            lines: self.lines,
-            bytes_uncompressed: self.bytes,
-            bytes_gzip,
-            checksum: None,
+        let (
+            mut bytes_gzip,
+            mut upload_local_path,
+            mut upload_remote_path,
+            mut manifest_path,
+            mut content_encoding,
+        ) = if self.compression.is_enabled() {
+            let compressed_bytes = self
+                .compression
+                .compress_file(&fileset.active_path, &fileset.compressed_path)
+                .await?;
+            if !self.config.dry_run {
+                let _ = fs::remove_file(&fileset.active_path).await;
+            }
+            (
+                compressed_bytes,
+                fileset.compressed_path.clone(),
+                fileset.remote_compressed.clone(),
+                fileset.manifest_path.clone(),
+                self.compression.content_encoding().map(str::to_string),
+            )
+        } else {
+            move_to_queue(&fileset.active_path, &fileset.queued_raw_path).await?;
+            (
+                self.bytes,
+                fileset.queued_raw_path.clone(),
+                fileset.remote_active.clone(),
+                format!("segments/{}", fileset.active_name),
+                None,
+            )
         };
-        let entry = SegmentEntry::new(self.seq, manifest_path.clone(), stats.clone());
+        let mut key_id = None;
+        if let Some(keys) = &self.config.keyring {
+            upload_remote_path.push_str(SEALED_SUFFIX);
+            (upload_local_path, bytes_gzip) =
+                seal_file(keys, &upload_remote_path, &upload_local_path).await?;
+            manifest_path.push_str(SEALED_SUFFIX);
+            content_encoding = None;
+            key_id = Some(keys.info().key_id);
+        }
+        let checkpoints = std::mem::take(&mut self.pending_checkpoints);
+        let checksum = sha256_file(&upload_local_path).await?;
+        let stats = self.current_stats(bytes_gzip, Some(checksum));
+        let mut entry = SegmentEntry::new(self.seq, manifest_path.clone(), stats.clone());
+        entry.compression = self.compression.content_encoding().map(str::to_string);
+        entry.key_id = key_id;
 
         Ok(SegmentClosed {
             entry,
             stats,
             files: fileset,
-            checkpoint,
+            checkpoints,
             upload_local_path,
             upload_remote_path,
             content_encoding,
         })
     }
 
+    /// Copy what has been written to the active segment so far into the preview object.
+    pub async fn preview(&mut self) -> Result<Option<SegmentPreview>> {
+        if self.lines == 0 {
+            return Ok(None);
+        }
+        self.file
+            .as_mut()
+            .context("segment writer missing active file handle")?
+            .flush()
+            .await?;
+
+        let active_path = self.fileset.active_path.clone();
+        let compression = self.compression;
+        let (mut name, mut local_path, mut bytes_gzip, mut content_encoding) =
+            if compression.is_enabled() {
+                let name = format!("{}.jsonl{}", PREVIEW_STEM, compression.extension());
+                let local_path = self.spool.queued_segment_path(&name);
+                let bytes = compression.compress_file(&active_path, &local_path).await?;
+                let encoding = compression.content_encoding().map(str::to_string);
+                (name, local_path, bytes, encoding)
+            } else {
+                let name = format!("{}.jsonl", PREVIEW_STEM);
+                let local_path = self.spool.queued_raw_segment_path(&name);
+                fs::copy(&active_path, &local_path).await.with_context(|| {
+                    format!("failed to copy {} for preview", active_path.display())
+                })?;
+                (name, local_path, self.bytes, None)
+            };
+        if self.config.keyring.is_some() {
+            name.push_str(SEALED_SUFFIX);
+            content_encoding = None;
+        }
+        let manifest_path = format!("segments/{}", name);
+        let remote_path = format!(
+            "{}/{}",
+            self.config.object_prefix().trim_end_matches('/'),
+            manifest_path
+        );
+        let mut key_id = None;
+        if let Some(keys) = &self.config.keyring {
+            (local_path, bytes_gzip) = seal_file(keys, &remote_path, &local_path).await?;
+            key_id = Some(keys.info().key_id);
+        }
+        let checksum = sha256_file(&local_path).await?;
+        let mut entry = SegmentEntry::new(
+            self.seq,
+            manifest_path,
+            self.current_stats(bytes_gzip, Some(checksum)),
+        );
+        entry.compression = compression.content_encoding().map(str::to_string);
+        entry.key_id = key_id;
+        Ok(Some(SegmentPreview {
+            entry,
+            local_path,
+            remote_path,
+            content_encoding,
+        }))
+    }
+
+    fn current_stats(&self, bytes_gzip: u64, checksum: Option<String>) -> SegmentStats {
+        SegmentStats {
+            first_ts: self.first_ts.unwrap_or(0),
+            last_ts: self.last_ts.unwrap_or(self.first_ts.unwrap_or(0)),
+            lines: self.lines,
+            bytes_uncompressed: self.bytes,
+            bytes_gzip,
+            checksum,
+            event_types: self.event_types.clone(),
+            invalid_json: self.invalid_json,
+            inferred_timestamps: self.inferred_timestamps,
+            dropped: self.dropped,
+            truncated: self.truncated,
+            tokens: self.usage.current().clone(),
+            redactions: self.redactions.clone(),
+        }
+    }
+
     async fn start_next_segment(&mut self) -> Result<()> {
         self.seq += 1;
         self.bytes = 0;
         self.lines = 0;
         self.first_ts = None;
         self.last_ts = None;
-        self.pending_checkpoint = None;
+        self.event_types.clear();
+        self.invalid_json = 0;
+        self.inferred_timestamps = 0;
+        self.dropped = 0;
+        self.truncated = 0;
+        self.redactions.clear();
+        self.usage.reset();
+        self.pending_checkpoints.clear();
         self.opened_at = Instant::now();
         let prefix = self.config.object_prefix();
-        self.fileset = SegmentFileSet::new(&self.spool, &prefix, self.seq)?;
+        self.fileset = SegmentFileSet::new(&self.spool, &prefix, self.seq, self.compression)?;
         let next_file = open_segment_file(&self.fileset.active_path).await?;
         self.file = Some(next_file);
         Ok(())
@@ -329,10 +489,15 @@ impl SegmentWriter {
 }
 
 impl SegmentFileSet {
-    pub fn new(spool: &SpoolLayout, prefix: &str, seq: u32) -> Result<Self> {
+    pub fn new(
+        spool: &SpoolLayout,
+        prefix: &str,
+        seq: u32,
+        compression: Compression,
+    ) -> Result<Self> {
         let file_stem = format!("{}-{:06}", SEGMENT_PREFIX, seq);
         let active_name = format!("{}.jsonl", file_stem);
-        let compressed_name = format!("{}.jsonl.gz", file_stem);
+        let compressed_name = format!("{}.jsonl{}", file_stem, compression.extension());
         let active_path = spool.active_segment_path(&active_name);
         let compressed_path = spool.queued_segment_path(&compressed_name);
         let queued_raw_path = spool.queued_raw_segment_path(&active_name);
@@ -374,25 +539,40 @@ async fn open_segment_file(path: &Path) -> Result<File> {
     Ok(file)
 }
 
-async fn gzip_file(source: &Path, dest: &Path) -> Result<u64> {
-    if let Some(parent) = dest.parent() {
-        ensure_dir(parent)?;
-    }
-    let mut reader = File::open(source)
+/// Replace `path` with an encrypted copy named `<path>.enc`, bound to the object path it
+/// uploads to; returns it and its size.
+async fn seal_file(keys: &Keyring, object_path: &str, path: &Path) -> Result<(PathBuf, u64)> {
+    let plaintext = fs::read(path)
         .await
-        .with_context(|| format!("failed to open {} for compression", source.display()))?;
-    let dest_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(dest)
+        .with_context(|| format!("failed to read {} for encryption", path.display()))?;
+    let sealed = keys.seal(object_path, &plaintext)?;
+    let mut dest = path.as_os_str().to_owned();
+    dest.push(SEALED_SUFFIX);
+    let dest = PathBuf::from(dest);
+    fs::write(&dest, &sealed)
+        .await
+        .with_context(|| format!("failed to write {}", dest.display()))?;
+    fs::remove_file(path)
+        .await
+        .with_context(|| format!("failed to remove {} after encryption", path.display()))?;
+    Ok((dest, sealed.len() as u64))
+}
+
+/// Hex sha256 of the object as uploaded, so readers can tell segment versions apart.
+async fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
         .await
-        .with_context(|| format!("failed to create gzip {}", dest.display()))?;
-    let mut encoder = GzipEncoder::new(dest_file);
-    tokio::io::copy(&mut reader, &mut encoder).await?;
-    encoder.shutdown().await?;
-    let meta = fs::metadata(dest).await?;
-    Ok(meta.len())
+        .with_context(|| format!("failed to open {} for checksum", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
 }
 
 async fn move_to_queue(source: &Path, dest: &Path) -> Result<()> {
@@ -3,16 +3,23 @@ use crate::util::ensure_dir;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use time::OffsetDateTime;
 use tokio::fs;
+use tokio::sync::Mutex;
 
 pub const META_EXTENSION: &str = "meta.json";
+/// Where dry-run "uploads" land so the local dashboard can still read them.
+pub const LOCAL_OBJECTS_DIR: &str = "local";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SpoolItemKind {
     Segment,
+    Preview,
     Manifest,
     Checkpoint,
+    Index,
+    Attachment,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +51,22 @@ pub struct SpoolQueue {
     layout: SpoolLayout,
 }
 
+/// Dry-run copies of "uploaded" objects under `<spool>/local`, pruned oldest-first so
+/// they never take more than `budget` bytes.
+#[derive(Debug)]
+pub struct LocalMirror {
+    layout: SpoolLayout,
+    budget: u64,
+    /// Bytes currently mirrored; `None` until the directory is first scanned.
+    used: Mutex<Option<u64>>,
+}
+
+struct MirroredFile {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
 impl SpoolLayout {
     pub fn new(root: PathBuf) -> Self {
         let active_dir = root.join("active");
@@ -61,6 +84,17 @@ impl SpoolLayout {
         Self::new(config.spool_dir.clone())
     }
 
+    /// Layout scoped to a single session so several sessions can share one spool root
+    /// without their segment and manifest file names colliding.
+    pub fn for_session(&self, sid: &str) -> Self {
+        Self {
+            root: self.root.clone(),
+            active_dir: self.active_dir.join(sid),
+            queue_dir: self.queue_dir.join(sid),
+            manifest_dir: self.manifest_dir.clone(),
+        }
+    }
+
     pub fn manifest_state_path(&self, sid: &str) -> PathBuf {
         self.manifest_dir.join(format!("{sid}.json"))
     }
@@ -85,6 +119,22 @@ impl SpoolLayout {
         self.queue_dir.join(name)
     }
 
+    /// Attachments sit next to the segments, prefixed so they cannot collide with them.
+    pub fn queued_attachment_path(&self, name: &str) -> PathBuf {
+        self.queue_dir.join(format!("attachment-{name}"))
+    }
+
+    /// Local mirror of a bucket object, e.g. `<spool>/local/sessions/<sid>/manifest.json`.
+    pub fn local_object_path(&self, object_path: &str) -> PathBuf {
+        let mut path = self.root.join(LOCAL_OBJECTS_DIR);
+        for part in object_path.split('/') {
+            if !part.is_empty() && part != "." && part != ".." {
+                path.push(part);
+            }
+        }
+        path
+    }
+
     pub fn ensure(&self) -> Result<()> {
         ensure_dir(&self.root)?;
         ensure_dir(&self.active_dir)?;
@@ -107,6 +157,90 @@ impl SpoolLayout {
     }
 }
 
+impl LocalMirror {
+    pub fn new(layout: SpoolLayout, budget: u64) -> Self {
+        Self {
+            layout,
+            budget,
+            used: Mutex::new(None),
+        }
+    }
+
+    /// Copy `source` to the mirror path of `object_path`. Objects larger than the whole
+    /// budget are not kept.
+    pub async fn keep(&self, object_path: &str, source: &Path) -> Result<()> {
+        let len = fs::metadata(source).await?.len();
+        if len > self.budget {
+            return Ok(());
+        }
+        let target = self.layout.local_object_path(object_path);
+        let mut used = self.used.lock().await;
+        let mut total = match *used {
+            Some(total) => total,
+            None => self.files().await?.iter().map(|file| file.len).sum(),
+        };
+        if let Ok(previous) = fs::metadata(&target).await {
+            total = total.saturating_sub(previous.len());
+        }
+        if let Some(parent) = target.parent() {
+            ensure_dir(parent)?;
+        }
+        total += fs::copy(source, &target).await?;
+        if total > self.budget {
+            total = self.prune(&target).await?;
+        }
+        *used = Some(total);
+        Ok(())
+    }
+
+    /// Remove the oldest copies other than `keep` until the mirror fits its budget;
+    /// returns the bytes left.
+    async fn prune(&self, keep: &Path) -> Result<u64> {
+        let mut files = self.files().await?;
+        files.sort_by_key(|file| file.modified);
+        let mut total: u64 = files.iter().map(|file| file.len).sum();
+        for file in files {
+            if total <= self.budget {
+                break;
+            }
+            if file.path == keep {
+                continue;
+            }
+            match fs::remove_file(&file.path).await {
+                Ok(()) => total -= file.len,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => total -= file.len,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(total)
+    }
+
+    async fn files(&self) -> Result<Vec<MirroredFile>> {
+        let mut files = Vec::new();
+        let mut dirs = vec![self.layout.root.join(LOCAL_OBJECTS_DIR)];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                } else {
+                    files.push(MirroredFile {
+                        path: entry.path(),
+                        len: metadata.len(),
+                        modified: metadata.modified()?,
+                    });
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
 impl SpoolQueue {
     pub fn new(layout: SpoolLayout) -> Self {
         Self { layout }
@@ -133,7 +267,25 @@ impl SpoolQueue {
 
     pub async fn list(&self) -> Result<Vec<SpoolEntry>> {
         let mut entries = Vec::new();
-        let mut dir = fs::read_dir(&self.layout.queue_dir).await?;
+        if fs::metadata(&self.layout.queue_dir).await.is_err() {
+            return Ok(entries);
+        }
+        let mut dirs = vec![self.layout.queue_dir.clone()];
+        let mut root = fs::read_dir(&self.layout.queue_dir).await?;
+        while let Some(entry) = root.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        for dir in dirs {
+            self.list_dir(&dir, &mut entries).await?;
+        }
+        entries.sort_by_key(|a| a.metadata.created_at);
+        Ok(entries)
+    }
+
+    async fn list_dir(&self, dir: &Path, entries: &mut Vec<SpoolEntry>) -> Result<()> {
+        let mut dir = fs::read_dir(dir).await?;
         let suffix = format!(".{}", META_EXTENSION);
         while let Some(entry) = dir.next_entry().await? {
             let path = entry.path();
@@ -159,8 +311,7 @@ impl SpoolQueue {
                 metadata,
             });
         }
-        entries.sort_by(|a, b| a.metadata.created_at.cmp(&b.metadata.created_at));
-        Ok(entries)
+        Ok(())
     }
 
     pub async fn mark_uploaded(&self, entry: &SpoolEntry) -> Result<()> {
@@ -173,3 +324,36 @@ impl SpoolQueue {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local_mirror_drops_the_oldest_copies_past_its_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let layout = SpoolLayout::new(dir.path().to_path_buf());
+        let source = dir.path().join("object");
+        std::fs::write(&source, vec![b'x'; 40]).unwrap();
+        let mirror = LocalMirror::new(layout.clone(), 100);
+        for name in ["a", "b", "c"] {
+            mirror
+                .keep(&format!("sessions/s/{name}"), &source)
+                .await
+                .unwrap();
+            // mtime granularity can be coarse; keep the copies' ages distinct.
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(!layout.local_object_path("sessions/s/a").exists());
+        assert!(layout.local_object_path("sessions/s/b").exists());
+        assert!(layout.local_object_path("sessions/s/c").exists());
+
+        // Rewriting an object replaces its bytes rather than adding to them.
+        mirror.keep("sessions/s/c", &source).await.unwrap();
+        assert!(layout.local_object_path("sessions/s/b").exists());
+
+        std::fs::write(&source, vec![b'x'; 200]).unwrap();
+        mirror.keep("sessions/s/big", &source).await.unwrap();
+        assert!(!layout.local_object_path("sessions/s/big").exists());
+    }
+}
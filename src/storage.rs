@@ -0,0 +1,698 @@
+use crate::attachments::content_hash;
+use crate::compression::CompressionKind;
+use crate::crypto::{Keyring, SEALED_SUFFIX, envelope_key_id, open_json, open_object};
+use crate::heartbeat::{HEARTBEAT_FILENAME, Heartbeat};
+use crate::index::{SessionIndex, SessionSummary};
+use crate::manifest::{Manifest, SegmentEntry, SessionStatus};
+use crate::spool::{SpoolLayout, SpoolQueue};
+use anyhow::{Context, Result, bail};
+use futures::stream::{self, StreamExt};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+
+/// Objects requested per Storage list call.
+const LIST_PAGE_SIZE: usize = 1000;
+/// Manifests fetched in parallel when building a session listing.
+const MANIFEST_FETCH_CONCURRENCY: usize = 16;
+
+#[derive(Clone)]
+pub struct StorageInspector {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    bucket: String,
+    keys: Option<Keyring>,
+}
+
+impl StorageInspector {
+    pub fn new(base_url: String, api_key: String, bucket: String) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent("agent-uploader/ui/0.1")
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(Self {
+            client,
+            base_url,
+            api_key,
+            bucket,
+            keys: None,
+        })
+    }
+
+    /// Open encrypted manifests, segments and index objects with these keys.
+    pub fn with_keys(mut self, keys: Option<Keyring>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Every session id with a manifest under `root_prefix`.
+    pub async fn list_sessions(&self, root_prefix: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}/", root_prefix.trim_start_matches('/'));
+        let mut result = Vec::new();
+        for name in self.list_object_names(&prefix).await? {
+            let candidate = name.strip_prefix(&prefix).unwrap_or(&name);
+            if candidate.ends_with("manifest.json") {
+                if let Some((sid, _)) = candidate.split_once('/') {
+                    if !sid.is_empty() {
+                        result.push(sid.to_string());
+                    }
+                }
+            }
+        }
+        result.sort();
+        result.dedup();
+        Ok(result)
+    }
+
+    /// Merged rows of every uploader's session index, or `None` if no index exists yet.
+    pub async fn fetch_session_index(
+        &self,
+        root_prefix: &str,
+    ) -> Result<Option<BTreeMap<String, SessionSummary>>> {
+        let prefix = format!("{}/", SessionIndex::remote_dir(root_prefix));
+        let mut indexes = Vec::new();
+        for name in self.list_object_names(&prefix).await? {
+            let file = name.rsplit('/').next().unwrap_or(&name);
+            if !file.ends_with(".json") {
+                continue;
+            }
+            let object_path = format!("{prefix}{file}");
+            let parsed = self
+                .fetch_object_if_exists(&object_path)
+                .await
+                .and_then(|bytes| {
+                    bytes
+                        .map(|bytes| {
+                            let bytes = open_json(self.keys.as_ref(), &object_path, bytes)?;
+                            anyhow::Ok(serde_json::from_slice::<SessionIndex>(&bytes)?)
+                        })
+                        .transpose()
+                        .with_context(|| format!("invalid session index {object_path}"))
+                });
+            match parsed {
+                Ok(Some(index)) => indexes.push(index),
+                Ok(None) => {}
+                Err(err) => tracing::warn!(error = %err, "skipping session index"),
+            }
+        }
+        if indexes.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(SessionIndex::merge(indexes)))
+    }
+
+    /// Names of all objects under `prefix`, following list offsets until Storage
+    /// returns a short page.
+    async fn list_object_names(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut offset = 0;
+        loop {
+            let objects = self.list_page(prefix, offset).await?;
+            let returned = objects.len();
+            names.extend(
+                objects
+                    .iter()
+                    .filter_map(|item| item.get("name").and_then(|v| v.as_str()))
+                    .map(str::to_string),
+            );
+            if returned < LIST_PAGE_SIZE {
+                break;
+            }
+            offset += returned;
+        }
+        Ok(names)
+    }
+
+    async fn list_page(&self, prefix: &str, offset: usize) -> Result<Vec<Value>> {
+        let url = format!(
+            "{}/storage/v1/object/list/{}",
+            self.base_url.trim_end_matches('/'),
+            self.bucket
+        );
+        let body = serde_json::json!({
+            "prefix": prefix,
+            "limit": LIST_PAGE_SIZE,
+            "offset": offset,
+            "sortBy": { "column": "name", "order": "asc" },
+            "depth": 2
+        });
+        let response = self
+            .client
+            .post(url)
+            .header("authorization", format!("Bearer {}", self.api_key))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("failed to list {}: {} {}", prefix, status, text);
+        }
+
+        let text = response.text().await?;
+        let value: Value = serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse storage list payload: {text}"))?;
+        Ok(match value {
+            Value::Array(array) => array,
+            Value::Object(obj) => obj
+                .get("data")
+                .and_then(|data| data.as_array())
+                .cloned()
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        })
+    }
+
+    pub async fn fetch_manifest(&self, root_prefix: &str, sid: &str) -> Result<Manifest> {
+        self.fetch_manifest_if_exists(root_prefix, sid)
+            .await?
+            .with_context(|| format!("manifest not found for {sid}"))
+    }
+
+    /// Like [`StorageInspector::fetch_manifest`], but a missing object yields `None`.
+    pub async fn fetch_manifest_if_exists(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+    ) -> Result<Option<Manifest>> {
+        let object_path = format!(
+            "{}/{}/{}",
+            root_prefix.trim_start_matches('/'),
+            sid,
+            crate::manifest::MANIFEST_FILENAME
+        );
+        let Some(bytes) = self.fetch_object_if_exists(&object_path).await? else {
+            return Ok(None);
+        };
+        let bytes = open_json(self.keys.as_ref(), &object_path, bytes)
+            .with_context(|| format!("failed to decrypt manifest for {sid}"))?;
+        let manifest: Manifest = serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to parse manifest for {sid}"))?;
+        Ok(Some(manifest))
+    }
+
+    pub async fn fetch_segment_lines(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+        manifest: &Manifest,
+        segment: &SegmentEntry,
+    ) -> Result<Vec<Value>> {
+        let object_path = StorageInspector::segment_object_path(root_prefix, sid, &segment.path);
+        let bytes = self.fetch_object_bytes(&object_path).await?;
+        decode_segment(&object_path, manifest, segment, bytes, self.keys.as_ref())
+    }
+
+    pub fn segment_object_path(root_prefix: &str, sid: &str, path: &str) -> String {
+        format!("{}/{}/{}", root_prefix.trim_start_matches('/'), sid, path)
+    }
+
+    pub async fn fetch_object_bytes(&self, object_path: &str) -> Result<Vec<u8>> {
+        self.fetch_object_if_exists(object_path)
+            .await?
+            .with_context(|| format!("failed to fetch object {object_path}: not found"))
+    }
+
+    pub async fn fetch_object_if_exists(&self, object_path: &str) -> Result<Option<Vec<u8>>> {
+        let url = format!(
+            "{}/storage/v1/object/{}/{}",
+            self.base_url.trim_end_matches('/'),
+            self.bucket,
+            object_path.trim_start_matches('/')
+        );
+        let response = self
+            .client
+            .get(url)
+            .header("authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            // Storage reports missing objects as 404, or as 400 with a not_found body.
+            if status == StatusCode::NOT_FOUND || text.contains("not_found") {
+                return Ok(None);
+            }
+            anyhow::bail!(
+                "failed to fetch object {}: {} {}",
+                object_path,
+                status,
+                text
+            );
+        }
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+}
+
+/// Read-only view of the sessions this machine produced, for when there is no bucket to
+/// read back from (`--dry-run`, `--upload-url`). Manifests come from the state dir;
+/// objects from the dry-run mirror under `<spool>/local`, then from the upload queue.
+#[derive(Clone)]
+pub struct LocalInspector {
+    state_dir: PathBuf,
+    spool_layout: SpoolLayout,
+    keys: Option<Keyring>,
+}
+
+impl LocalInspector {
+    pub fn new(state_dir: PathBuf, spool_layout: SpoolLayout) -> Self {
+        Self {
+            state_dir,
+            spool_layout,
+            keys: None,
+        }
+    }
+
+    /// Open encrypted segment objects with these keys; local manifests are kept in clear.
+    pub fn with_keys(mut self, keys: Option<Keyring>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<String>> {
+        let mut sids = Vec::new();
+        let mut dir = match fs::read_dir(&self.state_dir).await {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(sids),
+            Err(err) => return Err(err.into()),
+        };
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(sid) = path.file_stem().and_then(|stem| stem.to_str()) {
+                sids.push(sid.to_string());
+            }
+        }
+        sids.sort();
+        Ok(sids)
+    }
+
+    pub async fn fetch_manifest_if_exists(&self, sid: &str) -> Result<Option<Manifest>> {
+        let path = self.state_dir.join(format!("{sid}.json"));
+        if fs::metadata(&path).await.is_err() {
+            return Ok(None);
+        }
+        read_manifest(&path).await.map(Some)
+    }
+
+    pub async fn fetch_segment_lines(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+        manifest: &Manifest,
+        segment: &SegmentEntry,
+    ) -> Result<Vec<Value>> {
+        let object_path = StorageInspector::segment_object_path(root_prefix, sid, &segment.path);
+        let bytes = self
+            .fetch_object_if_exists(&object_path)
+            .await?
+            .with_context(|| format!("object {object_path} is not available locally"))?;
+        decode_segment(&object_path, manifest, segment, bytes, self.keys.as_ref())
+    }
+
+    pub async fn fetch_object_if_exists(&self, object_path: &str) -> Result<Option<Vec<u8>>> {
+        let object_path = object_path.trim_start_matches('/');
+        let mirrored = self.spool_layout.local_object_path(object_path);
+        if fs::metadata(&mirrored).await.is_ok() {
+            return Ok(Some(fs::read(&mirrored).await?));
+        }
+        // Not uploaded yet: the newest queued copy is what the bucket will end up with.
+        let queued = SpoolQueue::new(self.spool_layout.clone())
+            .list()
+            .await?
+            .into_iter()
+            .rfind(|entry| entry.metadata.remote_path.trim_start_matches('/') == object_path);
+        match queued {
+            Some(entry) => Ok(Some(fs::read(&entry.data_path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The heartbeat is written locally before any upload, so read it from the active dir.
+    pub async fn fetch_heartbeat_if_exists(&self, sid: &str) -> Result<Option<Vec<u8>>> {
+        let path = self
+            .spool_layout
+            .for_session(sid)
+            .active_dir
+            .join(HEARTBEAT_FILENAME);
+        match fs::read(&path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    #[default]
+    Sid,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionQuery {
+    /// Only sessions whose sid starts with this.
+    pub prefix: Option<String>,
+    /// Case-insensitive substring filters on the session's metadata.
+    pub repo: Option<String>,
+    pub model: Option<String>,
+    pub host: Option<String>,
+    pub sort: SessionSort,
+    pub descending: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl SessionQuery {
+    fn filters_metadata(&self) -> bool {
+        self.repo.is_some() || self.model.is_some() || self.host.is_some()
+    }
+
+    fn matches(&self, session: &SessionSummary) -> bool {
+        fn contains(value: &Option<String>, needle: &Option<String>) -> bool {
+            match needle {
+                None => true,
+                Some(needle) => value
+                    .as_deref()
+                    .is_some_and(|value| value.to_lowercase().contains(&needle.to_lowercase())),
+            }
+        }
+        contains(&session.repo, &self.repo)
+            && contains(&session.model, &self.model)
+            && contains(&session.host, &self.host)
+    }
+}
+
+#[derive(Debug)]
+pub struct SessionPage {
+    pub sessions: Vec<SessionSummary>,
+    /// Sessions matching the query across all pages.
+    pub total: usize,
+    pub next_offset: Option<usize>,
+}
+
+impl SessionPage {
+    fn new(sessions: Vec<SessionSummary>, total: usize, offset: usize, limit: usize) -> Self {
+        let end = offset.saturating_add(limit);
+        Self {
+            sessions,
+            total,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+}
+
+/// Where the dashboard reads sessions from: the bucket when Supabase credentials are
+/// configured, otherwise this machine's spool and state dirs.
+#[derive(Clone)]
+pub enum SessionStore {
+    Remote(StorageInspector),
+    Local(LocalInspector),
+}
+
+impl SessionStore {
+    /// One page of sessions matching `query`. When the bucket has a session index the
+    /// listing is served from it without listing session folders, unless no indexed sid
+    /// matches the query's prefix; without an index every session comes from its
+    /// manifest. Live rows may lag their manifest (the index is rewritten less often), so
+    /// those are re-read. Sorting by sid without metadata filters slices the id list
+    /// first, so only the requested page can need manifests.
+    pub async fn list_sessions(
+        &self,
+        root_prefix: &str,
+        query: &SessionQuery,
+    ) -> Result<SessionPage> {
+        let (mut sids, mut indexed) = match self {
+            Self::Remote(storage) => {
+                let index = storage
+                    .fetch_session_index(root_prefix)
+                    .await
+                    .unwrap_or_else(|err| {
+                        tracing::warn!(error = %err, "failed to read session index");
+                        None
+                    })
+                    .unwrap_or_default();
+                let hit = index.keys().any(|sid| {
+                    query
+                        .prefix
+                        .as_deref()
+                        .is_none_or(|prefix| sid.starts_with(prefix))
+                });
+                if hit {
+                    (index.keys().cloned().collect(), index)
+                } else {
+                    tracing::debug!("session index has no matching sessions; scanning manifests");
+                    (storage.list_sessions(root_prefix).await?, index)
+                }
+            }
+            Self::Local(local) => (local.list_sessions().await?, BTreeMap::new()),
+        };
+        if let Some(prefix) = &query.prefix {
+            sids.retain(|sid| sid.starts_with(prefix.as_str()));
+        }
+        let limit = query.limit.unwrap_or(usize::MAX);
+
+        if query.sort == SessionSort::Sid && !query.filters_metadata() {
+            let total = sids.len();
+            if query.descending {
+                sids.reverse();
+            }
+            let page: Vec<String> = sids.into_iter().skip(query.offset).take(limit).collect();
+            let sessions = self.summaries(root_prefix, page, &mut indexed).await;
+            return Ok(SessionPage::new(sessions, total, query.offset, limit));
+        }
+
+        let mut sessions = self.summaries(root_prefix, sids, &mut indexed).await;
+        sessions.retain(|session| query.matches(session));
+        match query.sort {
+            SessionSort::Sid => {}
+            SessionSort::CreatedAt => sessions.sort_by_key(|session| session.created_at),
+            SessionSort::UpdatedAt => sessions.sort_by_key(|session| session.updated_at),
+        }
+        if query.descending {
+            sessions.reverse();
+        }
+        let total = sessions.len();
+        let sessions = sessions
+            .into_iter()
+            .skip(query.offset)
+            .take(limit)
+            .collect();
+        Ok(SessionPage::new(sessions, total, query.offset, limit))
+    }
+
+    /// Summaries for `sids` in order: finished indexed rows as-is, live rows and the rest
+    /// from their manifests, fetched with bounded concurrency. A live row is kept when its
+    /// manifest cannot be read; unindexed sessions whose manifest cannot be read are skipped.
+    async fn summaries(
+        &self,
+        root_prefix: &str,
+        sids: Vec<String>,
+        indexed: &mut BTreeMap<String, SessionSummary>,
+    ) -> Vec<SessionSummary> {
+        let lookups: Vec<(String, Option<SessionSummary>)> = sids
+            .into_iter()
+            .map(|sid| {
+                let row = indexed.remove(&sid);
+                (sid, row)
+            })
+            .collect();
+        stream::iter(lookups)
+            .map(|(sid, row)| async move {
+                if let Some(row) = &row
+                    && row.status == SessionStatus::Complete
+                {
+                    return Some(row.clone());
+                }
+                match self.fetch_manifest(root_prefix, &sid).await {
+                    Ok(manifest) => {
+                        let fresh = SessionSummary::from(&manifest);
+                        match row {
+                            Some(row) if row.updated_at > fresh.updated_at => Some(row),
+                            _ => Some(fresh),
+                        }
+                    }
+                    Err(err) => {
+                        tracing::warn!(session = %sid, error = %err, "failed to fetch manifest");
+                        row
+                    }
+                }
+            })
+            .buffered(MANIFEST_FETCH_CONCURRENCY)
+            .filter_map(|summary| async move { summary })
+            .collect()
+            .await
+    }
+
+    pub async fn fetch_manifest(&self, root_prefix: &str, sid: &str) -> Result<Manifest> {
+        match self {
+            Self::Remote(storage) => storage.fetch_manifest(root_prefix, sid).await,
+            Self::Local(local) => local
+                .fetch_manifest_if_exists(sid)
+                .await?
+                .with_context(|| format!("manifest not found for {sid}")),
+        }
+    }
+
+    pub async fn fetch_segment_lines(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+        manifest: &Manifest,
+        segment: &SegmentEntry,
+    ) -> Result<Vec<Value>> {
+        match self {
+            Self::Remote(storage) => {
+                storage
+                    .fetch_segment_lines(root_prefix, sid, manifest, segment)
+                    .await
+            }
+            Self::Local(local) => {
+                local
+                    .fetch_segment_lines(root_prefix, sid, manifest, segment)
+                    .await
+            }
+        }
+    }
+
+    /// Keys for opening encrypted objects, if any were given.
+    pub fn keys(&self) -> Option<&Keyring> {
+        match self {
+            Self::Remote(storage) => storage.keys.as_ref(),
+            Self::Local(local) => local.keys.as_ref(),
+        }
+    }
+
+    /// The segment object as stored (possibly compressed or encrypted), for callers that cache it.
+    pub async fn fetch_segment_bytes(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+        path: &str,
+    ) -> Result<Vec<u8>> {
+        let object_path = StorageInspector::segment_object_path(root_prefix, sid, path);
+        match self {
+            Self::Remote(storage) => storage.fetch_object_bytes(&object_path).await,
+            Self::Local(local) => local
+                .fetch_object_if_exists(&object_path)
+                .await?
+                .with_context(|| format!("object {object_path} is not available locally")),
+        }
+    }
+
+    /// The original text of an attachment stored at `path` (relative to the session),
+    /// checked against its hash.
+    pub async fn fetch_attachment(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+        hash: &str,
+        path: &str,
+    ) -> Result<String> {
+        let bytes = self.fetch_segment_bytes(root_prefix, sid, path).await?;
+        let object_path = StorageInspector::segment_object_path(root_prefix, sid, path);
+        let body = String::from_utf8(decode_object(&object_path, None, bytes, self.keys())?)
+            .with_context(|| format!("attachment {path} is not UTF-8"))?;
+        if content_hash(&body) != hash {
+            bail!("attachment {path} does not match its hash");
+        }
+        Ok(body)
+    }
+
+    pub async fn fetch_heartbeat_if_exists(
+        &self,
+        root_prefix: &str,
+        sid: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let object_path = Heartbeat::remote_path(&format!("{root_prefix}/{sid}"));
+        let bytes = match self {
+            Self::Remote(storage) => storage.fetch_object_if_exists(&object_path).await?,
+            Self::Local(local) => local.fetch_heartbeat_if_exists(sid).await?,
+        };
+        bytes
+            .map(|bytes| open_json(self.keys(), &object_path, bytes))
+            .transpose()
+    }
+}
+
+async fn read_manifest(path: &Path) -> Result<Manifest> {
+    let bytes = fs::read(path)
+        .await
+        .with_context(|| format!("failed to read manifest {}", path.display()))?;
+    serde_json::from_slice(&bytes)
+        .with_context(|| format!("failed to parse manifest {}", path.display()))
+}
+
+/// Decode the segment object at `object_path` as stored, using the codec recorded in its
+/// manifest entry. A segment the manifest records a key for, or any segment of an
+/// encrypted session, has to be sealed, and with the recorded key.
+pub fn decode_segment(
+    object_path: &str,
+    manifest: &Manifest,
+    segment: &SegmentEntry,
+    bytes: Vec<u8>,
+    keys: Option<&Keyring>,
+) -> Result<Vec<Value>> {
+    if segment.key_id.is_some() || manifest.encryption.is_some() {
+        if !object_path.ends_with(SEALED_SUFFIX) {
+            bail!(
+                "segment {} is recorded as encrypted but stored in clear",
+                segment.path
+            );
+        }
+        if let Some(expected) = &segment.key_id {
+            let found = envelope_key_id(&bytes)
+                .with_context(|| format!("failed to decode {}", segment.path))?;
+            if found != expected {
+                bail!(
+                    "segment {} is sealed with key {found}, not {expected} as recorded",
+                    segment.path
+                );
+            }
+        }
+    }
+    let raw = decode_object(object_path, segment.compression.as_deref(), bytes, keys)
+        .with_context(|| format!("failed to decode {}", segment.path))?;
+    parse_ndjson_lines(&raw)
+}
+
+/// The plain bytes of the object at `object_path` as stored: decrypted when the name ends
+/// in `.enc`, then decompressed with `encoding` if given, or else by the `.gz`/`.zst`
+/// extension.
+pub fn decode_object(
+    object_path: &str,
+    encoding: Option<&str>,
+    bytes: Vec<u8>,
+    keys: Option<&Keyring>,
+) -> Result<Vec<u8>> {
+    let (path, bytes) = open_object(keys, object_path, bytes)?;
+    CompressionKind::detect(path, encoding).decompress(bytes)
+}
+
+/// Lines that are not JSON (e.g. `--format text` logs) come back as strings, the same
+/// way the live stream sends them.
+pub fn parse_ndjson_lines(bytes: &[u8]) -> Result<Vec<Value>> {
+    let mut lines = Vec::new();
+    for line in bytes.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let value = serde_json::from_slice(line)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(line).into_owned()));
+        lines.push(value);
+    }
+    Ok(lines)
+}
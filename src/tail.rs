@@ -1,9 +1,20 @@
+use crate::checkpoint::CheckpointRules;
+use crate::config::WatchConfig;
+use crate::format::{
+    FormatAdapter, FormatDetector, SessionFormat, TextFormat, pointer_string, pointer_timestamp,
+};
+use crate::metadata::SessionMetadata;
+use crate::redact::Redactor;
 use anyhow::{Context, Result};
-use serde_json::{Map, Value};
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use time::OffsetDateTime;
-use time::format_description::well_known::Rfc3339;
+use time::format_description::FormatItem;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
 use tokio::fs::{self, File, OpenOptions};
 use tokio::io::SeekFrom;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
@@ -14,8 +25,17 @@ pub struct SessionEvent {
     pub json: Option<Value>,
     pub timestamp: OffsetDateTime,
     pub unix_ts: i64,
+    /// No timestamp could be read from the line; `timestamp` was carried over from the
+    /// previous event, or is the session's start time when there was none.
+    pub timestamp_inferred: bool,
     pub event_type: Option<String>,
     pub checkpoint: Option<CheckpointTrigger>,
+    /// Session details this line carries, as read by the format adapter.
+    pub metadata: Option<SessionMetadata>,
+    /// Secrets replaced in `raw`, per detector.
+    pub redactions: BTreeMap<String, u64>,
+    /// String fields cut short by `--event-rules`.
+    pub truncated: u64,
 }
 
 #[derive(Debug)]
@@ -24,8 +44,43 @@ pub struct TailReader {
     file: File,
     offset: u64,
     carry: Vec<u8>,
+    parser: EventParser,
 }
 
+/// Turns raw lines into [`SessionEvent`]s through a [`FormatAdapter`], with any
+/// configured field locations and checkpoint rules taking precedence over the adapter's.
+/// With `--format auto` the adapter is picked from the opening lines (see
+/// [`FormatDetector`]) unless the caller already knows it. It also remembers the
+/// last timestamp it read so lines without one can inherit it, so each file should get
+/// its own parser.
+#[derive(Debug, Clone, Default)]
+pub struct EventParser {
+    adapter: Option<Arc<dyn FormatAdapter>>,
+    detector: FormatDetector,
+    fields: EventFields,
+    checkpoints: Option<CheckpointRules>,
+    redactor: Option<Redactor>,
+    last_timestamp: Option<OffsetDateTime>,
+}
+
+/// JSON pointers to an event's timestamp and type, each tried in order. Empty lists
+/// leave the format adapter's own locations in place.
+#[derive(Debug, Clone, Default)]
+pub struct EventFields {
+    pub timestamp: Vec<String>,
+    pub event_type: Vec<String>,
+}
+
+/// Date-times without an offset are taken as UTC.
+const NAIVE_TIMESTAMP_FORMATS: &[&[FormatItem<'static>]] = &[
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]"),
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]"),
+    format_description!("[year]/[month]/[day] [hour]:[minute]:[second][optional [.[subsecond]]]"),
+];
+
+/// Epoch values at or above this are taken as milliseconds (year 5138 in seconds).
+const EPOCH_MILLIS_THRESHOLD: f64 = 1e11;
+
 #[derive(Debug)]
 pub struct TailBatch {
     pub events: Vec<SessionEvent>,
@@ -38,10 +93,18 @@ pub struct CheckpointTrigger {
     pub git_commit: Option<String>,
     pub branch: Option<String>,
     pub payload: Option<Value>,
+    /// Close the active segment at this event.
+    pub rotate: bool,
 }
 
 impl TailReader {
     pub async fn new(path: PathBuf) -> Result<Self> {
+        Self::open_at(path, 0, EventParser::default()).await
+    }
+
+    /// Open the file and resume tailing from `offset`, typically a value previously
+    /// returned by [`TailReader::consumed_offset`].
+    pub async fn open_at(path: PathBuf, offset: u64, parser: EventParser) -> Result<Self> {
         let file = OpenOptions::new()
             .read(true)
             .open(&path)
@@ -50,11 +113,21 @@ impl TailReader {
         Ok(Self {
             path,
             file,
-            offset: 0,
+            offset,
             carry: Vec::new(),
+            parser,
         })
     }
 
+    pub fn parser(&self) -> &EventParser {
+        &self.parser
+    }
+
+    /// Byte offset up to which complete lines have been emitted.
+    pub fn consumed_offset(&self) -> u64 {
+        self.offset - self.carry.len() as u64
+    }
+
     pub async fn poll(&mut self) -> Result<Option<TailBatch>> {
         let metadata = match fs::metadata(&self.path).await {
             Ok(meta) => meta,
@@ -94,7 +167,7 @@ impl TailReader {
                     line.pop();
                 }
                 if !line.is_empty() {
-                    events.push(SessionEvent::from_line(line));
+                    events.push(self.parser.parse(line));
                 }
                 start = idx + 1;
             }
@@ -108,6 +181,19 @@ impl TailReader {
         Ok(Some(TailBatch { events, truncated }))
     }
 
+    /// Emit any trailing bytes that were not terminated by a newline. Only useful once the
+    /// writer is known to be done with the file, e.g. when importing a finished session.
+    pub fn take_remainder(&mut self) -> Option<SessionEvent> {
+        let mut line = std::mem::take(&mut self.carry);
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        if line.is_empty() {
+            return None;
+        }
+        Some(self.parser.parse(line))
+    }
+
     pub async fn reset(&mut self) -> Result<()> {
         self.file = OpenOptions::new()
             .read(true)
@@ -121,97 +207,210 @@ impl TailReader {
 }
 
 impl SessionEvent {
+    /// Parse a line on its own, detecting its format.
     pub fn from_line(raw: Vec<u8>) -> Self {
-        match serde_json::from_slice::<Value>(&raw) {
-            Ok(value) => {
-                let timestamp = extract_timestamp(&value).unwrap_or_else(OffsetDateTime::now_utc);
-                let event_type = extract_event_type(&value);
-                let checkpoint = extract_checkpoint(event_type.as_deref(), &value);
-                Self {
-                    raw,
-                    json: Some(value),
-                    timestamp,
-                    unix_ts: timestamp.unix_timestamp(),
-                    event_type,
-                    checkpoint,
-                }
+        EventParser::default().parse(raw)
+    }
+}
+
+impl EventParser {
+    /// `checkpoints` replaces the adapter's built-in rules when set.
+    pub fn new(
+        format: SessionFormat,
+        fields: EventFields,
+        checkpoints: Option<CheckpointRules>,
+    ) -> Self {
+        Self {
+            adapter: format.adapter(),
+            detector: FormatDetector::default(),
+            fields,
+            checkpoints,
+            redactor: None,
+            last_timestamp: None,
+        }
+    }
+
+    /// Redact secrets from each line before it is decoded.
+    pub fn with_redactor(mut self, redactor: Option<Redactor>) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Lines before the first readable timestamp take the session's `created_at`, which
+    /// backfill sets to the file's first timestamp.
+    pub fn from_config(config: &WatchConfig) -> Self {
+        let mut parser = Self::new(
+            config.format,
+            config.event_fields.clone(),
+            config.checkpoints.clone(),
+        )
+        .with_redactor(config.redactor.clone());
+        parser.last_timestamp = Some(config.created_at);
+        parser
+    }
+
+    /// Use a format detected elsewhere, e.g. from the file's head when tailing resumes
+    /// mid-file. An explicit `--format` still wins.
+    pub fn with_detected(mut self, adapter: Option<Arc<dyn FormatAdapter>>) -> Self {
+        if self.adapter.is_none() {
+            self.adapter = adapter;
+        }
+        self
+    }
+
+    /// The adapter in use, once the format is known.
+    pub fn adapter(&self) -> Option<&dyn FormatAdapter> {
+        self.adapter.as_deref()
+    }
+
+    pub fn parse(&mut self, raw: Vec<u8>) -> SessionEvent {
+        let (raw, json, redactions) = match &self.redactor {
+            Some(redactor) => {
+                let redacted = redactor.redact(raw);
+                (redacted.raw, redacted.json, redacted.counts)
             }
-            Err(_) => {
-                let timestamp = OffsetDateTime::now_utc();
-                Self {
-                    raw,
-                    json: None,
-                    timestamp,
-                    unix_ts: timestamp.unix_timestamp(),
-                    event_type: None,
-                    checkpoint: None,
-                }
+            None => {
+                let json = serde_json::from_slice::<Value>(&raw).ok();
+                (raw, json, BTreeMap::new())
             }
+        };
+        if self.adapter.is_none() {
+            self.adapter = self.detector.observe(&raw, json.as_ref());
         }
-    }
-}
+        // Lines read before the format settles are handled as text.
+        let adapter = self.adapter.clone().unwrap_or_else(|| Arc::new(TextFormat));
+        if self.checkpoints.is_none() && self.adapter.is_some() {
+            self.checkpoints = Some(adapter.checkpoint_rules());
+        }
+        let provisional;
+        let checkpoints = match &self.checkpoints {
+            Some(rules) => rules,
+            None => {
+                provisional = adapter.checkpoint_rules();
+                &provisional
+            }
+        };
 
-pub fn poll_interval(duration: Duration) -> tokio::time::Interval {
-    tokio::time::interval(duration)
-}
+        let parsed_ts = if self.fields.timestamp.is_empty() {
+            adapter.timestamp(&raw, json.as_ref())
+        } else {
+            json.as_ref()
+                .and_then(|value| pointer_timestamp(value, &self.fields.timestamp))
+        };
+        let timestamp_inferred = parsed_ts.is_none();
+        let timestamp = parsed_ts
+            .or(self.last_timestamp)
+            .unwrap_or_else(OffsetDateTime::now_utc);
+        self.last_timestamp = Some(timestamp);
 
-fn extract_timestamp(value: &Value) -> Option<OffsetDateTime> {
-    match value {
-        Value::Object(map) => map
-            .get("timestamp")
-            .and_then(|v| v.as_str())
-            .and_then(|ts| OffsetDateTime::parse(ts, &Rfc3339).ok()),
-        _ => None,
+        let event_type = if self.fields.event_type.is_empty() {
+            adapter.event_type(&raw, json.as_ref())
+        } else {
+            json.as_ref()
+                .and_then(|value| pointer_string(value, &self.fields.event_type))
+        };
+        let (checkpoint, metadata) = match &json {
+            Some(value) => (
+                checkpoints.evaluate(event_type.as_deref(), value),
+                adapter.metadata(event_type.as_deref(), value),
+            ),
+            None => (None, None),
+        };
+        SessionEvent {
+            raw,
+            json,
+            timestamp,
+            unix_ts: timestamp.unix_timestamp(),
+            timestamp_inferred,
+            event_type,
+            checkpoint,
+            metadata,
+            redactions,
+            truncated: 0,
+        }
     }
 }
 
-fn extract_event_type(value: &Value) -> Option<String> {
+/// Read a timestamp from epoch seconds or milliseconds (numbers or numeric strings),
+/// RFC 3339, RFC 2822, or an ISO-like date-time without offset.
+pub fn parse_timestamp(value: &Value) -> Option<OffsetDateTime> {
     match value {
-        Value::Object(map) => map
-            .get("type")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
+        Value::Number(number) => number.as_f64().and_then(from_epoch),
+        Value::String(text) => {
+            let text = text.trim();
+            if let Ok(epoch) = text.parse::<f64>() {
+                return from_epoch(epoch);
+            }
+            OffsetDateTime::parse(text, &Rfc3339)
+                .or_else(|_| OffsetDateTime::parse(&text.replacen(' ', "T", 1), &Rfc3339))
+                .or_else(|_| OffsetDateTime::parse(text, &Rfc2822))
+                .ok()
+                .or_else(|| {
+                    NAIVE_TIMESTAMP_FORMATS.iter().find_map(|format| {
+                        PrimitiveDateTime::parse(text, format)
+                            .ok()
+                            .map(PrimitiveDateTime::assume_utc)
+                    })
+                })
+        }
         _ => None,
     }
 }
 
-fn extract_checkpoint(event_type: Option<&str>, value: &Value) -> Option<CheckpointTrigger> {
-    if event_type != Some("compacted") {
+fn from_epoch(epoch: f64) -> Option<OffsetDateTime> {
+    if !epoch.is_finite() {
         return None;
     }
-    let payload = match value {
-        Value::Object(map) => map,
-        _ => return None,
+    let nanos = if epoch.abs() >= EPOCH_MILLIS_THRESHOLD {
+        epoch * 1e6
+    } else {
+        epoch * 1e9
     };
+    OffsetDateTime::from_unix_timestamp_nanos(nanos as i128).ok()
+}
 
-    let checkpoint_obj = payload
-        .get("checkpoint")
-        .and_then(|v| v.as_object())
-        .cloned()
-        .or_else(|| payload.get("detail").and_then(|v| v.as_object()).cloned())
-        .unwrap_or_else(Map::new);
-
-    let git_commit = checkpoint_obj
-        .get("git_commit")
-        .or_else(|| checkpoint_obj.get("git"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-    let branch = checkpoint_obj
-        .get("branch")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-    let label = checkpoint_obj
-        .get("label")
-        .or_else(|| checkpoint_obj.get("summary"))
-        .or_else(|| payload.get("note"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string())
-        .or_else(|| Some("compacted".to_string()));
-
-    Some(CheckpointTrigger {
-        label,
-        git_commit,
-        branch,
-        payload: Some(Value::Object(checkpoint_obj.clone())),
-    })
+pub fn poll_interval(duration: Duration) -> tokio::time::Interval {
+    tokio::time::interval(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn timestamps_in_several_formats() {
+        let expected = OffsetDateTime::from_unix_timestamp(1_760_000_000).unwrap();
+        for value in [
+            json!(1_760_000_000),
+            json!(1_760_000_000_000u64),
+            json!("1760000000"),
+            json!("2025-10-09T08:53:20Z"),
+            json!("2025-10-09 08:53:20+00:00"),
+            json!("Thu, 09 Oct 2025 08:53:20 +0000"),
+            json!("2025-10-09T08:53:20"),
+            json!("2025-10-09 08:53:20.000"),
+        ] {
+            assert_eq!(parse_timestamp(&value), Some(expected), "{value}");
+        }
+        assert_eq!(parse_timestamp(&json!("yesterday")), None);
+    }
+
+    #[test]
+    fn missing_timestamps_inherit_the_previous_one() {
+        let mut parser = EventParser::new(
+            SessionFormat::Codex,
+            EventFields {
+                timestamp: vec!["/ts".to_string()],
+                event_type: vec!["/kind".to_string()],
+            },
+            None,
+        );
+        let first = parser.parse(br#"{"ts": 1760000000, "kind": "a"}"#.to_vec());
+        assert!(!first.timestamp_inferred);
+        assert_eq!(first.event_type.as_deref(), Some("a"));
+        let second = parser.parse(b"not json".to_vec());
+        assert!(second.timestamp_inferred);
+        assert_eq!(second.timestamp, first.timestamp);
+    }
 }
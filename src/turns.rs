@@ -0,0 +1,486 @@
+use crate::format::{pointer_string, pointer_timestamp};
+use crate::rollout::{ContentItem, EventMsg, ResponseItem, RolloutItem};
+use crate::usage::{TokenUsage, UsageTracker, total_usage};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+const TIMESTAMP_POINTERS: &[&str] = &["/timestamp", "/created_at", "/time", "/ts"];
+
+/// One user turn: the prompt and everything the agent did in response, up to the next
+/// prompt. Lines before the first prompt (session setup) form a turn without one.
+#[derive(Debug, Clone, Serialize)]
+pub struct Turn {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Session-wide index of the turn's first line, and one past its last.
+    pub line_start: u64,
+    pub line_end: u64,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub ended_at: Option<OffsetDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    /// The user interrupted the turn.
+    pub aborted: bool,
+    pub items: Vec<TurnItem>,
+    pub tokens: TokenUsage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TurnItem {
+    Message {
+        role: String,
+        text: String,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            with = "time::serde::rfc3339::option"
+        )]
+        timestamp: Option<OffsetDateTime>,
+    },
+    Reasoning {
+        text: String,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            with = "time::serde::rfc3339::option"
+        )]
+        timestamp: Option<OffsetDateTime>,
+    },
+    /// A tool call with its output once that arrives.
+    ToolCall {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        call_id: Option<String>,
+        name: String,
+        input: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        success: Option<bool>,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            with = "time::serde::rfc3339::option"
+        )]
+        started_at: Option<OffsetDateTime>,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            with = "time::serde::rfc3339::option"
+        )]
+        completed_at: Option<OffsetDateTime>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration_ms: Option<i64>,
+    },
+    /// Output whose call is not in the session (e.g. it was cut off by a truncation).
+    ToolOutput {
+        call_id: String,
+        output: String,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            with = "time::serde::rfc3339::option"
+        )]
+        timestamp: Option<OffsetDateTime>,
+    },
+}
+
+/// Group a session's lines into turns.
+///
+/// Codex records each prompt twice: as a `user_message` event and as a user
+/// `response_item` message. Prompts come from the events when the session has any, and
+/// otherwise from user messages that are not injected context (`<environment_context>`,
+/// `<user_instructions>`). Chat-format lines start a turn at every `user` message. Token
+/// usage is attributed to the turn its `token_count` event falls in.
+pub fn group_turns<'a>(lines: impl IntoIterator<Item = &'a Value>) -> Vec<Turn> {
+    let lines: Vec<(&Value, RolloutItem)> = lines
+        .into_iter()
+        .map(|line| (line, RolloutItem::from_json(line)))
+        .collect();
+    let prompt_events = lines
+        .iter()
+        .any(|(_, item)| matches!(item, RolloutItem::EventMsg(EventMsg::UserMessage { .. })));
+    let mut grouper = Grouper {
+        prompt_events,
+        turns: Vec::new(),
+        current: Turn::new(0, 0, None),
+        model: None,
+        usage: UsageTracker::default(),
+        calls: HashMap::new(),
+    };
+    for (line_idx, (line, item)) in lines.into_iter().enumerate() {
+        grouper.push(line_idx as u64, line, item);
+    }
+    grouper.finish()
+}
+
+struct Grouper {
+    prompt_events: bool,
+    turns: Vec<Turn>,
+    current: Turn,
+    model: Option<String>,
+    usage: UsageTracker,
+    /// `call_id` to (turn index, item index) of calls still open for their output.
+    calls: HashMap<String, (usize, usize)>,
+}
+
+impl Grouper {
+    fn push(&mut self, line_idx: u64, line: &Value, item: RolloutItem) {
+        let timestamp = pointer_timestamp(line, TIMESTAMP_POINTERS);
+        if let Some(prompt) = self.prompt(line, &item) {
+            if self.current.line_end > self.current.line_start {
+                self.close_turn(line_idx);
+            }
+            self.current.user_message = Some(prompt);
+        }
+        self.current.line_end = line_idx + 1;
+        if let Some(timestamp) = timestamp {
+            self.current.started_at.get_or_insert(timestamp);
+            self.current.ended_at = Some(timestamp);
+        }
+
+        match item {
+            RolloutItem::TurnContext(context) => {
+                if let Some(model) = context.model {
+                    self.usage.set_model(model.clone());
+                    self.model = Some(model.clone());
+                    self.current.model = Some(model);
+                }
+            }
+            RolloutItem::EventMsg(EventMsg::TokenCount(count)) => self.usage.record(&count),
+            RolloutItem::EventMsg(EventMsg::TurnAborted { .. }) => self.current.aborted = true,
+            RolloutItem::ResponseItem(item) => self.push_response_item(item, timestamp),
+            RolloutItem::Unknown(_) => {
+                if let Some(role) = chat_role(line)
+                    && role != "user"
+                    && let Some(text) = chat_text(line)
+                {
+                    self.current.items.push(TurnItem::Message {
+                        role,
+                        text,
+                        timestamp,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_response_item(&mut self, item: ResponseItem, timestamp: Option<OffsetDateTime>) {
+        let (call_id, name, input) = match item {
+            ResponseItem::Message { role, content, .. } if role == "assistant" => {
+                self.current.items.push(TurnItem::Message {
+                    role,
+                    text: join_text(content.iter().filter_map(ContentItem::text)),
+                    timestamp,
+                });
+                return;
+            }
+            ResponseItem::Reasoning { summary, .. } => {
+                let text = join_text(summary.iter().filter_map(|part| part.text()));
+                if !text.is_empty() {
+                    self.current
+                        .items
+                        .push(TurnItem::Reasoning { text, timestamp });
+                }
+                return;
+            }
+            ResponseItem::FunctionCall {
+                name,
+                arguments,
+                call_id,
+                ..
+            } => (Some(call_id), name, arguments),
+            ResponseItem::CustomToolCall {
+                call_id,
+                name,
+                input,
+                ..
+            } => (Some(call_id), name, input),
+            ResponseItem::LocalShellCall {
+                call_id, action, ..
+            } => (call_id, "local_shell".to_string(), action.to_string()),
+            ResponseItem::FunctionCallOutput { call_id, output } => {
+                let success = output.success();
+                self.complete_call(call_id, output.text().to_string(), success, timestamp);
+                return;
+            }
+            ResponseItem::CustomToolCallOutput { call_id, output } => {
+                self.complete_call(call_id, output, None, timestamp);
+                return;
+            }
+            _ => return,
+        };
+        if let Some(call_id) = &call_id {
+            self.calls.insert(
+                call_id.clone(),
+                (self.turns.len(), self.current.items.len()),
+            );
+        }
+        self.current.items.push(TurnItem::ToolCall {
+            call_id,
+            name,
+            input,
+            output: None,
+            success: None,
+            started_at: timestamp,
+            completed_at: None,
+            duration_ms: None,
+        });
+    }
+
+    fn complete_call(
+        &mut self,
+        call_id: String,
+        text: String,
+        ok: Option<bool>,
+        timestamp: Option<OffsetDateTime>,
+    ) {
+        let call =
+            self.calls
+                .remove(&call_id)
+                .and_then(|(turn, item)| match self.turns.get_mut(turn) {
+                    Some(turn) => turn.items.get_mut(item),
+                    None => self.current.items.get_mut(item),
+                });
+        match call {
+            Some(TurnItem::ToolCall {
+                output,
+                success,
+                started_at,
+                completed_at,
+                duration_ms,
+                ..
+            }) => {
+                *output = Some(text);
+                *success = ok;
+                *completed_at = timestamp;
+                *duration_ms = millis_between(*started_at, timestamp);
+            }
+            _ => self.current.items.push(TurnItem::ToolOutput {
+                call_id,
+                output: text,
+                timestamp,
+            }),
+        }
+    }
+
+    /// The prompt text if this line starts a new turn.
+    fn prompt(&self, line: &Value, item: &RolloutItem) -> Option<String> {
+        match item {
+            RolloutItem::EventMsg(EventMsg::UserMessage { message, .. }) => Some(message.clone()),
+            RolloutItem::ResponseItem(ResponseItem::Message { role, content, .. })
+                if !self.prompt_events && role == "user" =>
+            {
+                let text = join_text(content.iter().filter_map(ContentItem::text));
+                (!text.trim_start().starts_with('<')).then_some(text)
+            }
+            RolloutItem::Unknown(_) if chat_role(line).as_deref() == Some("user") => {
+                Some(chat_text(line).unwrap_or_default())
+            }
+            _ => None,
+        }
+    }
+
+    fn close_turn(&mut self, next_line: u64) {
+        let index = self.turns.len() + 1;
+        let next = Turn::new(index, next_line, self.model.clone());
+        let mut turn = std::mem::replace(&mut self.current, next);
+        turn.tokens = total_usage(self.usage.current().values());
+        turn.duration_ms = millis_between(turn.started_at, turn.ended_at);
+        self.usage.reset();
+        self.turns.push(turn);
+    }
+
+    fn finish(mut self) -> Vec<Turn> {
+        if self.current.line_end > self.current.line_start {
+            let end = self.current.line_end;
+            self.close_turn(end);
+        }
+        self.turns
+    }
+}
+
+impl Turn {
+    fn new(index: usize, line_start: u64, model: Option<String>) -> Self {
+        Self {
+            index,
+            user_message: None,
+            model,
+            line_start,
+            line_end: line_start,
+            started_at: None,
+            ended_at: None,
+            duration_ms: None,
+            aborted: false,
+            items: Vec::new(),
+            tokens: TokenUsage::default(),
+        }
+    }
+}
+
+fn chat_role(line: &Value) -> Option<String> {
+    pointer_string(line, &["/role", "/message/role"])
+}
+
+/// Chat content is a string or a list of `{"type":"text","text":...}` blocks.
+fn chat_text(line: &Value) -> Option<String> {
+    let content = line
+        .pointer("/content")
+        .or_else(|| line.pointer("/message/content"))?;
+    match content {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(blocks) => {
+            Some(join_text(blocks.iter().filter_map(|block| {
+                block.get("text").and_then(Value::as_str)
+            })))
+            .filter(|text| !text.is_empty())
+        }
+        _ => None,
+    }
+}
+
+fn join_text<'a>(parts: impl Iterator<Item = &'a str>) -> String {
+    parts.collect::<Vec<_>>().join("\n")
+}
+
+fn millis_between(start: Option<OffsetDateTime>, end: Option<OffsetDateTime>) -> Option<i64> {
+    Some((end? - start?).whole_milliseconds() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn codex_session_groups_into_turns() {
+        let at = |secs: u32| format!("2025-10-09T08:53:{secs:02}Z");
+        let lines = vec![
+            json!({"timestamp": at(0), "type": "session_meta", "payload": {"cwd": "/w"}}),
+            json!({"timestamp": at(1), "type": "response_item", "payload": {"type": "message", "role": "user",
+                "content": [{"type": "input_text", "text": "<environment_context>/w</environment_context>"}]}}),
+            json!({"timestamp": at(2), "type": "event_msg", "payload": {"type": "user_message", "message": "list files"}}),
+            json!({"timestamp": at(2), "type": "turn_context", "payload": {"model": "gpt-5"}}),
+            json!({"timestamp": at(3), "type": "response_item", "payload": {"type": "function_call",
+                "name": "shell", "arguments": "{\"command\":[\"ls\"]}", "call_id": "c1"}}),
+            json!({"timestamp": at(5), "type": "response_item", "payload": {"type": "function_call_output",
+                "call_id": "c1", "output": "a.txt"}}),
+            json!({"timestamp": at(6), "type": "event_msg", "payload": {"type": "token_count",
+                "info": {"total_token_usage": {"input_tokens": 100, "output_tokens": 10, "total_tokens": 110}}}}),
+            json!({"timestamp": at(7), "type": "response_item", "payload": {"type": "message", "role": "assistant",
+                "content": [{"type": "output_text", "text": "a.txt"}]}}),
+            json!({"timestamp": at(9), "type": "event_msg", "payload": {"type": "user_message", "message": "thanks"}}),
+            json!({"timestamp": at(10), "type": "event_msg", "payload": {"type": "token_count",
+                "info": {"total_token_usage": {"input_tokens": 150, "output_tokens": 15, "total_tokens": 165}}}}),
+        ];
+        let turns = group_turns(&lines);
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[0].user_message, None);
+        assert_eq!((turns[0].line_start, turns[0].line_end), (0, 2));
+
+        let turn = &turns[1];
+        assert_eq!(turn.user_message.as_deref(), Some("list files"));
+        assert_eq!(turn.model.as_deref(), Some("gpt-5"));
+        assert_eq!(turn.duration_ms, Some(5000));
+        assert_eq!(turn.tokens.total_tokens, 110);
+        assert_eq!(turn.items.len(), 2);
+        let TurnItem::ToolCall {
+            name,
+            output,
+            duration_ms,
+            ..
+        } = &turn.items[0]
+        else {
+            panic!("expected a tool call");
+        };
+        assert_eq!(name, "shell");
+        assert_eq!(output.as_deref(), Some("a.txt"));
+        assert_eq!(*duration_ms, Some(2000));
+
+        assert_eq!(turns[2].model.as_deref(), Some("gpt-5"));
+        assert_eq!(turns[2].tokens.total_tokens, 55);
+    }
+
+    #[test]
+    fn late_tool_output_and_aborts_land_in_their_turn() {
+        let lines = vec![
+            json!({"type": "event_msg", "payload": {"type": "user_message", "message": "run it"}}),
+            json!({"type": "response_item", "payload": {"type": "function_call",
+                "name": "shell", "arguments": "{}", "call_id": "c1"}}),
+            json!({"type": "event_msg", "payload": {"type": "turn_aborted", "reason": "interrupted"}}),
+            json!({"type": "event_msg", "payload": {"type": "user_message", "message": "again"}}),
+            // The first call's output shows up after its turn was closed.
+            json!({"type": "response_item", "payload": {"type": "function_call_output",
+                "call_id": "c1", "output": "done"}}),
+            json!({"type": "response_item", "payload": {"type": "function_call_output",
+                "call_id": "missing", "output": "orphan"}}),
+        ];
+        let turns = group_turns(&lines);
+        assert_eq!(turns.len(), 2);
+        assert!(turns[0].aborted);
+        assert!(!turns[1].aborted);
+        let TurnItem::ToolCall { output, .. } = &turns[0].items[0] else {
+            panic!("expected a tool call");
+        };
+        assert_eq!(output.as_deref(), Some("done"));
+        assert_eq!(turns[1].items.len(), 1);
+        assert!(matches!(
+            &turns[1].items[0],
+            TurnItem::ToolOutput { call_id, .. } if call_id == "missing"
+        ));
+    }
+
+    #[test]
+    fn prompts_come_from_user_messages_without_events() {
+        let user = |text: &str| {
+            json!({"type": "response_item", "payload": {"type": "message", "role": "user",
+                "content": [{"type": "input_text", "text": text}]}})
+        };
+        let lines = vec![
+            user("<user_instructions>be brief</user_instructions>"),
+            user("first"),
+            json!({"type": "response_item", "payload": {"type": "message", "role": "assistant",
+                "content": [{"type": "output_text", "text": "ok"}]}}),
+            user("second"),
+        ];
+        let turns = group_turns(&lines);
+        let prompts: Vec<_> = turns
+            .iter()
+            .map(|turn| turn.user_message.as_deref())
+            .collect();
+        assert_eq!(prompts, [None, Some("first"), Some("second")]);
+        assert_eq!((turns[1].line_start, turns[1].line_end), (1, 3));
+    }
+
+    #[test]
+    fn chat_transcripts_turn_at_each_user_message() {
+        let lines = vec![
+            json!({"role": "system", "content": "setup", "ts": 1760000000}),
+            json!({"message": {"role": "user", "content": [{"type": "text", "text": "hi"}]}, "ts": 1760000001}),
+            json!({"message": {"role": "assistant", "content": "hello"}, "ts": 1760000004}),
+            json!({"role": "user", "content": "bye", "ts": 1760000005}),
+        ];
+        let turns = group_turns(&lines);
+        assert_eq!(turns.len(), 3);
+        assert!(matches!(
+            &turns[0].items[..],
+            [TurnItem::Message { role, .. }] if role == "system"
+        ));
+        assert_eq!(turns[1].user_message.as_deref(), Some("hi"));
+        assert_eq!(turns[1].duration_ms, Some(3000));
+        assert!(matches!(
+            &turns[1].items[..],
+            [TurnItem::Message { role, text, .. }] if role == "assistant" && text == "hello"
+        ));
+        assert_eq!(turns[2].user_message.as_deref(), Some("bye"));
+    }
+}
@@ -1,19 +1,30 @@
+use crate::attachments::{self, ATTACHMENTS_DIR};
+use crate::cache::SegmentCache;
 use crate::config::{UploadConfig, WatchConfig};
-use crate::manifest::Manifest;
+use crate::heartbeat::Heartbeat;
+use crate::index::SessionSummary;
+use crate::live::{LiveHub, LiveSubscription};
+use crate::manifest::{Manifest, SegmentEntry};
+use crate::spool::SpoolLayout;
+use crate::storage::{
+    LocalInspector, SessionQuery, SessionSort, SessionStore, StorageInspector, decode_segment,
+};
+use crate::turns::{Turn, group_turns};
+use crate::usage::{Pricing, UsageReport};
 use anyhow::{Context, Result};
 use axum::extract::{Path, Query, State};
 use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use axum::{Json, Router};
-use flate2::read::GzDecoder;
-use reqwest::Client;
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use tower::ServiceBuilder;
@@ -21,205 +32,118 @@ use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
 
 const MAX_LINES_DEFAULT: usize = 5000;
+const TURNS_LIMIT_DEFAULT: usize = 100;
 
 #[derive(Clone)]
 struct UiState {
-    storage: Option<Arc<StorageInspector>>,
+    storage: Arc<SessionStore>,
+    cache: Option<Arc<SegmentCache>>,
+    live: Option<Arc<LiveHub>>,
+    shutdown: watch::Receiver<bool>,
     root_prefix: String,
     max_lines: usize,
+    pricing: Pricing,
 }
 
 impl UiState {
-    fn new(storage: Option<Arc<StorageInspector>>, config: &WatchConfig) -> Self {
+    fn new(
+        storage: Arc<SessionStore>,
+        cache: Option<Arc<SegmentCache>>,
+        live: Option<Arc<LiveHub>>,
+        shutdown: watch::Receiver<bool>,
+        config: &WatchConfig,
+    ) -> Self {
         Self {
             storage,
+            cache,
+            live,
+            shutdown,
             root_prefix: config.root_prefix.trim_end_matches('/').to_string(),
             max_lines: MAX_LINES_DEFAULT,
+            pricing: config.pricing.clone(),
         }
     }
 }
 
-#[derive(Clone)]
-struct StorageInspector {
-    client: Client,
-    base_url: String,
-    api_key: String,
-    bucket: String,
-}
-
-impl StorageInspector {
-    fn new(base_url: String, api_key: String, bucket: String) -> Result<Self> {
-        let client = Client::builder()
-            .user_agent("agent-uploader/ui/0.1")
-            .timeout(Duration::from_secs(30))
-            .build()?;
-        Ok(Self {
-            client,
-            base_url,
-            api_key,
-            bucket,
-        })
-    }
-
-    async fn list_session_manifests(&self, root_prefix: &str) -> Result<Vec<Manifest>> {
-        let mut manifests = Vec::new();
-        for sid in self.list_sessions(root_prefix).await? {
-            match self.fetch_manifest(root_prefix, &sid).await {
-                Ok(manifest) => manifests.push(manifest),
-                Err(err) => {
-                    tracing::warn!(session = %sid, error = %err, "failed to fetch manifest");
-                }
-            }
-        }
-        Ok(manifests)
-    }
-
-    async fn list_sessions(&self, root_prefix: &str) -> Result<Vec<String>> {
-        let url = format!(
-            "{}/storage/v1/object/list/{}",
-            self.base_url.trim_end_matches('/'),
-            self.bucket
-        );
-        let prefix = format!("{}/", root_prefix.trim_start_matches('/'));
-        let body = serde_json::json!({
-            "prefix": prefix,
-            "limit": 1000,
-            "offset": 0,
-            "sortBy": { "column": "name", "order": "asc" },
-            "depth": 2
-        });
-        let response = self
-            .client
-            .post(url)
-            .header("authorization", format!("Bearer {}", self.api_key))
-            .header("content-type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            anyhow::bail!("failed to list sessions: {} {}", status, text);
-        }
-
-        let text = response.text().await?;
-        let value: Value = serde_json::from_str(&text)
-            .with_context(|| format!("failed to parse storage list payload: {text}"))?;
-        let objects = match value {
-            Value::Array(array) => array,
-            Value::Object(obj) => obj
-                .get("data")
-                .and_then(|data| data.as_array())
-                .cloned()
-                .unwrap_or_default(),
-            _ => Vec::new(),
-        };
-
-        let mut result = Vec::new();
-        for item in objects {
-            let Some(name) = item.get("name").and_then(|v| v.as_str()) else {
-                continue;
-            };
-            let candidate = if let Some(stripped) = name.strip_prefix(&prefix) {
-                stripped
-            } else {
-                name
-            };
-            if candidate.ends_with("manifest.json") {
-                if let Some((sid, _)) = candidate.split_once('/') {
-                    if !sid.is_empty() {
-                        result.push(sid.to_string());
-                    }
-                }
-            }
-        }
-        result.sort();
-        result.dedup();
-        Ok(result)
-    }
-
-    async fn fetch_manifest(&self, root_prefix: &str, sid: &str) -> Result<Manifest> {
-        let object_path = format!(
-            "{}/{}/{}",
-            root_prefix.trim_start_matches('/'),
-            sid,
-            crate::manifest::MANIFEST_FILENAME
-        );
-        let bytes = self.fetch_object_bytes(&object_path).await?;
-        let manifest: Manifest = serde_json::from_slice(&bytes)
-            .with_context(|| format!("failed to parse manifest for {sid}"))?;
-        Ok(manifest)
-    }
-
-    async fn fetch_segment_lines(
-        &self,
-        root_prefix: &str,
-        sid: &str,
-        path: &str,
-    ) -> Result<Vec<Value>> {
-        let object_path = format!("{}/{}/{}", root_prefix.trim_start_matches('/'), sid, path);
-        let bytes = self.fetch_object_bytes(&object_path).await?;
-        let raw = if path.ends_with(".gz") {
-            let mut decoder = GzDecoder::new(bytes.as_slice());
-            let mut out = Vec::new();
-            use std::io::Read;
-            decoder.read_to_end(&mut out)?;
-            out
-        } else {
-            bytes
-        };
-        parse_ndjson_lines(&raw)
-    }
-
-    async fn fetch_object_bytes(&self, object_path: &str) -> Result<Vec<u8>> {
-        let url = format!(
-            "{}/storage/v1/object/{}/{}",
-            self.base_url.trim_end_matches('/'),
-            self.bucket,
-            object_path.trim_start_matches('/')
-        );
-        let response = self
-            .client
-            .get(url)
-            .header("authorization", format!("Bearer {}", self.api_key))
-            .send()
-            .await?;
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            anyhow::bail!(
-                "failed to fetch object {}: {} {}",
-                object_path,
-                status,
-                text
-            );
-        }
-        Ok(response.bytes().await?.to_vec())
-    }
+#[derive(Deserialize)]
+struct SessionsQuery {
+    /// Only sessions whose sid starts with this.
+    prefix: Option<String>,
+    repo: Option<String>,
+    model: Option<String>,
+    host: Option<String>,
+    sort: Option<SessionSort>,
+    order: Option<SortOrder>,
+    offset: Option<usize>,
+    limit: Option<usize>,
 }
 
-#[derive(Serialize)]
-struct SessionsResponse {
-    sessions: Vec<SessionPayload>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    Desc,
 }
 
 #[derive(Serialize)]
-struct SessionPayload {
-    sid: String,
-    manifest: Manifest,
+struct SessionsResponse {
+    sessions: Vec<SessionSummary>,
+    total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_offset: Option<usize>,
 }
 
 #[derive(Deserialize)]
 struct ReplayQuery {
     seq: Option<u32>,
     line_idx: Option<u64>,
+    /// `<seq>:<line_idx>`, as returned in `prev`/`next`; overrides `seq`/`line_idx`.
+    cursor: Option<String>,
+    direction: Option<ReplayDirection>,
     max_lines: Option<usize>,
+    /// Put attachment bodies back in place of their references.
+    #[serde(default)]
+    inline: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ReplayDirection {
+    #[default]
+    Backward,
+    Forward,
+}
+
+#[derive(Deserialize)]
+struct TurnsQuery {
+    #[serde(default)]
+    inline: bool,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TailQuery {
+    after: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TurnsResponse {
+    turns: Vec<Turn>,
+    total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_offset: Option<usize>,
 }
 
 #[derive(Serialize)]
 struct ReplayResponse {
     lines: Vec<Value>,
+    /// Last line before this window; page back with `direction=backward`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prev: Option<String>,
+    /// First line after this window; page forward with `direction=forward`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
 }
 
 pub struct UiHandle {
@@ -234,12 +158,13 @@ impl UiHandle {
     }
 }
 
-pub async fn spawn(config: Arc<WatchConfig>) -> Result<Option<UiHandle>> {
+pub async fn spawn(config: Arc<WatchConfig>, live: Arc<LiveHub>) -> Result<Option<UiHandle>> {
     if !config.ui.enabled {
         return Ok(None);
     }
 
-    let state = build_state(&config)?;
+    let (tx, mut rx) = watch::channel(false);
+    let state = build_state(&config, Some(live), rx.clone()).await?;
     let Some(dist_dir) = config.ui.dist_dir.clone() else {
         tracing::warn!("web ui disabled: no dist directory provided or found");
         return Ok(None);
@@ -263,7 +188,6 @@ pub async fn spawn(config: Arc<WatchConfig>) -> Result<Option<UiHandle>> {
         .unwrap_or_else(|_| "0.0.0.0:0".parse().unwrap());
     tracing::info!(address = %local_addr, "ui available");
 
-    let (tx, mut rx) = watch::channel(false);
     let server =
         axum::serve(listener, router.into_make_service()).with_graceful_shutdown(async move {
             let _ = rx.changed().await;
@@ -281,16 +205,32 @@ pub async fn spawn(config: Arc<WatchConfig>) -> Result<Option<UiHandle>> {
     }))
 }
 
-fn build_state(config: &Arc<WatchConfig>) -> Result<UiState> {
+async fn build_state(
+    config: &Arc<WatchConfig>,
+    live: Option<Arc<LiveHub>>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<UiState> {
     let storage = match &config.upload {
-        UploadConfig::Supabase { base_url, api_key } => Some(Arc::new(StorageInspector::new(
-            base_url.clone(),
-            api_key.clone(),
-            config.bucket.clone(),
-        )?)),
-        _ => None,
+        UploadConfig::Supabase { base_url, api_key } => SessionStore::Remote(
+            StorageInspector::new(base_url.clone(), api_key.clone(), config.bucket.clone())?
+                .with_keys(config.keyring.clone()),
+        ),
+        // Without read access to a bucket, serve what this machine wrote.
+        _ => SessionStore::Local(
+            LocalInspector::new(
+                config.manifest_state_dir.clone(),
+                SpoolLayout::from_config(config),
+            )
+            .with_keys(config.keyring.clone()),
+        ),
+    };
+    // Local objects are already on disk; only downloads are worth caching.
+    let cache = match storage {
+        SessionStore::Remote(_) => Some(Arc::new(SegmentCache::open(&config.ui.cache).await?)),
+        SessionStore::Local(_) => None,
     };
-    Ok(UiState::new(storage, config))
+    let storage = Arc::new(storage);
+    Ok(UiState::new(storage, cache, live, shutdown, config))
 }
 
 fn build_router(state: UiState, dist_dir: PathBuf) -> Router {
@@ -300,107 +240,434 @@ fn build_router(state: UiState, dist_dir: PathBuf) -> Router {
 
     Router::new()
         .route("/api/sessions", get(list_sessions))
+        .route("/api/sessions/:sid", get(session_manifest))
         .route("/api/sessions/:sid/replay", get(replay_session))
+        .route("/api/sessions/:sid/live", get(session_heartbeat))
+        .route("/api/sessions/:sid/usage", get(session_usage))
+        .route("/api/sessions/:sid/turns", get(session_turns))
+        .route("/api/sessions/:sid/tail", get(tail_session))
         .with_state(api_state)
         .nest_service("/", static_service)
         .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()))
 }
 
-async fn list_sessions(State(state): State<Arc<UiState>>) -> Response {
-    let Some(storage) = state.storage.clone() else {
-        return JsonError::service_unavailable("Supabase access not configured").into_response();
+async fn list_sessions(
+    State(state): State<Arc<UiState>>,
+    Query(params): Query<SessionsQuery>,
+) -> Response {
+    let storage = state.storage.clone();
+    let query = SessionQuery {
+        prefix: params.prefix.filter(|prefix| !prefix.is_empty()),
+        repo: params.repo.filter(|repo| !repo.is_empty()),
+        model: params.model.filter(|model| !model.is_empty()),
+        host: params.host.filter(|host| !host.is_empty()),
+        sort: params.sort.unwrap_or_default(),
+        descending: params.order == Some(SortOrder::Desc),
+        offset: params.offset.unwrap_or(0),
+        limit: params.limit.map(|limit| limit.max(1)),
     };
+    match storage.list_sessions(&state.root_prefix, &query).await {
+        Ok(page) => Json(SessionsResponse {
+            sessions: page.sessions,
+            total: page.total,
+            next_offset: page.next_offset,
+        })
+        .into_response(),
+        Err(err) => JsonError::internal(err).into_response(),
+    }
+}
 
-    match storage.list_session_manifests(&state.root_prefix).await {
-        Ok(manifests) => {
-            let sessions = manifests
-                .into_iter()
-                .map(|manifest| SessionPayload {
-                    sid: manifest.sid.clone(),
-                    manifest,
-                })
-                .collect();
-            Json(SessionsResponse { sessions }).into_response()
-        }
+async fn session_manifest(State(state): State<Arc<UiState>>, Path(sid): Path<String>) -> Response {
+    match state.storage.fetch_manifest(&state.root_prefix, &sid).await {
+        Ok(manifest) => Json(manifest).into_response(),
         Err(err) => JsonError::internal(err).into_response(),
     }
 }
 
+async fn session_usage(State(state): State<Arc<UiState>>, Path(sid): Path<String>) -> Response {
+    match state.storage.fetch_manifest(&state.root_prefix, &sid).await {
+        Ok(manifest) => Json(UsageReport::new(&manifest, &state.pricing)).into_response(),
+        Err(err) => JsonError::internal(err).into_response(),
+    }
+}
+
+/// Turns are grouped over the whole session (whether prompts come from events, tool
+/// calls answered in a later turn, running token totals), so every call reads and groups
+/// every segment; `offset`/`limit` only bound the response and the attachments inlined.
+async fn session_turns(
+    State(state): State<Arc<UiState>>,
+    Path(sid): Path<String>,
+    Query(params): Query<TurnsQuery>,
+) -> Response {
+    let manifest = match state.storage.fetch_manifest(&state.root_prefix, &sid).await {
+        Ok(manifest) => manifest,
+        Err(err) => return JsonError::internal(err).into_response(),
+    };
+    let mut lines = match session_lines(&state, &sid, &manifest).await {
+        Ok(lines) => lines,
+        Err(err) => return JsonError::internal(err).into_response(),
+    };
+    let offset = params.offset.unwrap_or(0);
+    let limit = params.limit.unwrap_or(TURNS_LIMIT_DEFAULT).max(1);
+    let mut turns = group_turns(&lines);
+    let total = turns.len();
+    let end = offset.saturating_add(limit).min(total);
+    // Only the page's lines get their attachments fetched; grouping again is cheap next
+    // to that.
+    if params.inline && offset < end {
+        let range = turns[offset].line_start as usize..turns[end - 1].line_end as usize;
+        if let Err(err) = inline_attachments(&state, &sid, &manifest, &mut lines[range]).await {
+            return JsonError::internal(err).into_response();
+        }
+        turns = group_turns(&lines);
+    }
+    Json(TurnsResponse {
+        turns: turns.into_iter().skip(offset).take(limit).collect(),
+        total,
+        next_offset: (end < total).then_some(end),
+    })
+    .into_response()
+}
+
 async fn replay_session(
     State(state): State<Arc<UiState>>,
     Path(sid): Path<String>,
     Query(params): Query<ReplayQuery>,
 ) -> Response {
-    let Some(storage) = state.storage.clone() else {
-        return JsonError::service_unavailable("Supabase access not configured").into_response();
+    let storage = state.storage.clone();
+    let anchor = match params.cursor.as_deref().map(parse_cursor) {
+        Some(Some(cursor)) => cursor,
+        Some(None) => {
+            return JsonError::bad_request("cursor must look like <seq>:<line_idx>")
+                .into_response();
+        }
+        None => (params.seq.unwrap_or(1), params.line_idx.unwrap_or(0)),
     };
-
-    let target_seq = params.seq.unwrap_or(1);
-    let target_line_idx = params.line_idx.unwrap_or(0);
-    let max_lines = params.max_lines.unwrap_or(state.max_lines);
+    let direction = params.direction.unwrap_or_default();
+    let max_lines = params.max_lines.unwrap_or(state.max_lines).max(1);
 
     match storage.fetch_manifest(&state.root_prefix, &sid).await {
-        Ok(manifest) => match collect_lines(
-            &storage,
-            &state.root_prefix,
-            &sid,
-            &manifest,
-            target_seq,
-            target_line_idx,
-            max_lines,
-        )
+        Ok(manifest) => {
+            match collect_lines(&state, &sid, &manifest, anchor, direction, max_lines).await {
+                Ok(mut response) => {
+                    if params.inline
+                        && let Err(err) =
+                            inline_attachments(&state, &sid, &manifest, &mut response.lines).await
+                    {
+                        return JsonError::internal(err).into_response();
+                    }
+                    Json(response).into_response()
+                }
+                Err(err) => JsonError::internal(err).into_response(),
+            }
+        }
+        Err(err) => JsonError::internal(err).into_response(),
+    }
+}
+
+async fn session_heartbeat(State(state): State<Arc<UiState>>, Path(sid): Path<String>) -> Response {
+    let storage = state.storage.clone();
+    match storage
+        .fetch_heartbeat_if_exists(&state.root_prefix, &sid)
         .await
-        {
-            Ok(lines) => Json(ReplayResponse { lines }).into_response(),
+    {
+        Ok(Some(bytes)) => match serde_json::from_slice::<Heartbeat>(&bytes) {
+            Ok(heartbeat) => Json(heartbeat).into_response(),
             Err(err) => JsonError::internal(err).into_response(),
         },
+        Ok(None) => JsonError::not_found("no heartbeat for session").into_response(),
         Err(err) => JsonError::internal(err).into_response(),
     }
 }
 
+/// Server-sent events for lines tailed by this process. Each event id is the line's
+/// `seq:line_idx` cursor; reconnecting clients resume after `Last-Event-ID` (or `?after=`).
+async fn tail_session(
+    State(state): State<Arc<UiState>>,
+    Path(sid): Path<String>,
+    Query(params): Query<TailQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(live) = state.live.clone() else {
+        return JsonError::service_unavailable("live tail not available").into_response();
+    };
+
+    let after = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or(params.after);
+    let after = match after.as_deref().map(parse_cursor) {
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
+            return JsonError::bad_request("cursor must look like <seq>:<line_idx>")
+                .into_response();
+        }
+        None => None,
+    };
+
+    let LiveSubscription { backlog, receiver } = live.subscribe(&sid, after);
+    // A lagging receiver ends the stream; the browser reconnects with Last-Event-ID and
+    // picks the missed lines up from the backlog.
+    let updates = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.ok().map(|line| (line, receiver))
+    });
+    let mut shutdown = state.shutdown.clone();
+    let events = stream::iter(backlog)
+        .chain(updates)
+        .map(|line| {
+            let (seq, line_idx) = line.position();
+            Event::default()
+                .id(format!("{seq}:{line_idx}"))
+                .json_data(&line)
+        })
+        .take_until(async move {
+            let _ = shutdown.wait_for(|stop| *stop).await;
+        });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn parse_cursor(raw: &str) -> Option<(u32, u64)> {
+    let (seq, line_idx) = raw.trim().split_once(':')?;
+    Some((seq.parse().ok()?, line_idx.parse().ok()?))
+}
+
+/// Fetch only the segments covering the requested window.
 async fn collect_lines(
-    storage: &StorageInspector,
-    root_prefix: &str,
+    state: &UiState,
     sid: &str,
     manifest: &Manifest,
-    target_seq: u32,
-    target_line_idx: u64,
+    anchor: (u32, u64),
+    direction: ReplayDirection,
     max_lines: usize,
-) -> Result<Vec<Value>> {
+) -> Result<ReplayResponse> {
+    let segments: Vec<&SegmentEntry> = manifest
+        .replay_segments()
+        .filter(|segment| segment.lines > 0)
+        .collect();
+    let window = plan_window(&segments, anchor, direction, max_lines);
+
     let mut lines = Vec::new();
-    for segment in &manifest.segments {
-        if segment.seq < target_seq {
-            let mut seg_lines = storage
-                .fetch_segment_lines(root_prefix, sid, &segment.path)
-                .await?;
-            lines.append(&mut seg_lines);
-        } else if segment.seq == target_seq {
-            let mut seg_lines = storage
-                .fetch_segment_lines(root_prefix, sid, &segment.path)
-                .await?;
-            let cutoff = (target_line_idx as usize + 1).min(seg_lines.len());
-            seg_lines.truncate(cutoff);
-            lines.append(&mut seg_lines);
-            break;
-        }
+    for slice in &window.slices {
+        let seg_lines = segment_lines(state, sid, manifest, segments[slice.segment]).await?;
+        let end = slice.end.min(seg_lines.len());
+        let start = slice.start.min(end);
+        lines.extend_from_slice(&seg_lines[start..end]);
     }
-    if lines.len() > max_lines {
-        let start = lines.len() - max_lines;
-        return Ok(lines[start..].to_vec());
+    let cursor = |(segment, line): (usize, u64)| format!("{}:{}", segments[segment].seq, line);
+    Ok(ReplayResponse {
+        lines,
+        prev: window.prev.map(cursor),
+        next: window.next.map(cursor),
+    })
+}
+
+/// Every line of the session, for views that need all of it at once.
+async fn session_lines(state: &UiState, sid: &str, manifest: &Manifest) -> Result<Vec<Value>> {
+    let mut lines = Vec::new();
+    for segment in manifest
+        .replay_segments()
+        .filter(|segment| segment.lines > 0)
+    {
+        lines.extend_from_slice(&segment_lines(state, sid, manifest, segment).await?);
     }
     Ok(lines)
 }
 
-fn parse_ndjson_lines(bytes: &[u8]) -> Result<Vec<Value>> {
-    let mut lines = Vec::new();
-    for line in bytes.split(|b| *b == b'\n') {
-        if line.is_empty() {
-            continue;
+/// Replace attachment references in `lines` with the stored bodies. Each distinct
+/// attachment is fetched once per request.
+async fn inline_attachments(
+    state: &UiState,
+    sid: &str,
+    manifest: &Manifest,
+    lines: &mut [Value],
+) -> Result<()> {
+    let mut hashes = BTreeSet::new();
+    lines
+        .iter()
+        .for_each(|line| attachments::references(line, &mut hashes));
+    let mut bodies = BTreeMap::new();
+    for hash in hashes {
+        let path = match manifest.attachments.get(&hash) {
+            Some(entry) => entry.path.clone(),
+            None => format!("{ATTACHMENTS_DIR}/{hash}"),
+        };
+        let body = state
+            .storage
+            .fetch_attachment(&state.root_prefix, sid, &hash, &path)
+            .await?;
+        bodies.insert(hash, body);
+    }
+    lines
+        .iter_mut()
+        .for_each(|line| attachments::inline(line, &bodies));
+    Ok(())
+}
+
+/// Decoded lines of one segment, through the segment cache when it is enabled.
+async fn segment_lines(
+    state: &UiState,
+    sid: &str,
+    manifest: &Manifest,
+    segment: &SegmentEntry,
+) -> Result<Arc<Vec<Value>>> {
+    let (storage, root_prefix) = (&state.storage, state.root_prefix.as_str());
+    let object_path = StorageInspector::segment_object_path(root_prefix, sid, &segment.path);
+    match state
+        .cache
+        .as_deref()
+        .zip(SegmentCache::key(&object_path, segment, manifest))
+    {
+        Some((cache, key)) => {
+            cache
+                .get_or_fetch(
+                    &key,
+                    segment.checksum.as_deref(),
+                    || storage.fetch_segment_bytes(root_prefix, sid, &segment.path),
+                    |raw| decode_segment(&object_path, manifest, segment, raw, storage.keys()),
+                )
+                .await
         }
-        let value: Value = serde_json::from_slice(line)?;
-        lines.push(value);
+        None => Ok(Arc::new(
+            storage
+                .fetch_segment_lines(root_prefix, sid, manifest, segment)
+                .await?,
+        )),
+    }
+}
+
+/// Lines `start..end` of `segments[segment]`.
+#[derive(Debug, PartialEq)]
+struct WindowSlice {
+    segment: usize,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Default)]
+struct ReplayWindow {
+    slices: Vec<WindowSlice>,
+    prev: Option<(usize, u64)>,
+    next: Option<(usize, u64)>,
+}
+
+/// Lay a window of at most `max_lines` lines over `segments` using their manifest line
+/// counts. Backward windows end at the anchor, forward windows start there (both
+/// inclusive). `prev` is the last line before the window and `next` the first after it,
+/// as `(segment index, line_idx)`.
+fn plan_window(
+    segments: &[&SegmentEntry],
+    anchor: (u32, u64),
+    direction: ReplayDirection,
+    max_lines: usize,
+) -> ReplayWindow {
+    let lines = |idx: usize| segments[idx].lines;
+    let Some(last) = segments.len().checked_sub(1) else {
+        return ReplayWindow::default();
+    };
+    let last_line = (last, lines(last) - 1);
+    // First line at or after the anchor, and last line at or before it.
+    let (at_or_after, at_or_before) = match segments.iter().position(|s| s.seq >= anchor.0) {
+        None => (None, Some(last_line)),
+        Some(idx) if segments[idx].seq > anchor.0 => (
+            Some((idx, 0)),
+            idx.checked_sub(1).map(|prev| (prev, lines(prev) - 1)),
+        ),
+        Some(idx) if anchor.1 >= lines(idx) => (
+            position_after(segments, (idx, lines(idx) - 1)),
+            Some((idx, lines(idx) - 1)),
+        ),
+        Some(idx) => (Some((idx, anchor.1)), Some((idx, anchor.1))),
+    };
+
+    let mut window = ReplayWindow::default();
+    let mut remaining = max_lines as u64;
+    match direction {
+        ReplayDirection::Backward => {
+            let Some(end) = at_or_before else {
+                window.next = at_or_after;
+                return window;
+            };
+            window.next = position_after(segments, end);
+            let (mut idx, mut stop) = (end.0, end.1 + 1);
+            loop {
+                let take = remaining.min(stop);
+                window.slices.push(WindowSlice {
+                    segment: idx,
+                    start: (stop - take) as usize,
+                    end: stop as usize,
+                });
+                remaining -= take;
+                if take < stop {
+                    window.prev = Some((idx, stop - take - 1));
+                    break;
+                }
+                if idx == 0 {
+                    break;
+                }
+                idx -= 1;
+                if remaining == 0 {
+                    window.prev = Some((idx, lines(idx) - 1));
+                    break;
+                }
+                stop = lines(idx);
+            }
+            window.slices.reverse();
+        }
+        ReplayDirection::Forward => {
+            let Some(begin) = at_or_after else {
+                window.prev = at_or_before;
+                return window;
+            };
+            window.prev = position_before(segments, begin);
+            let (mut idx, mut from) = begin;
+            loop {
+                let available = lines(idx) - from;
+                let take = remaining.min(available);
+                window.slices.push(WindowSlice {
+                    segment: idx,
+                    start: from as usize,
+                    end: (from + take) as usize,
+                });
+                remaining -= take;
+                if take < available {
+                    window.next = Some((idx, from + take));
+                    break;
+                }
+                if idx == last {
+                    break;
+                }
+                idx += 1;
+                if remaining == 0 {
+                    window.next = Some((idx, 0));
+                    break;
+                }
+                from = 0;
+            }
+        }
+    }
+    window
+}
+
+fn position_after(segments: &[&SegmentEntry], (idx, line): (usize, u64)) -> Option<(usize, u64)> {
+    if line + 1 < segments[idx].lines {
+        Some((idx, line + 1))
+    } else if idx + 1 < segments.len() {
+        Some((idx + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn position_before(segments: &[&SegmentEntry], (idx, line): (usize, u64)) -> Option<(usize, u64)> {
+    if line > 0 {
+        Some((idx, line - 1))
+    } else {
+        idx.checked_sub(1)
+            .map(|prev| (prev, segments[prev].lines - 1))
     }
-    Ok(lines)
 }
 
 struct JsonError {
@@ -416,6 +683,20 @@ impl JsonError {
         }
     }
 
+    fn bad_request(msg: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: msg.into(),
+        }
+    }
+
+    fn not_found(msg: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: msg.into(),
+        }
+    }
+
     fn service_unavailable(msg: impl Into<String>) -> Self {
         Self {
             status: StatusCode::SERVICE_UNAVAILABLE,
@@ -432,3 +713,81 @@ impl IntoResponse for JsonError {
         (self.status, headers, body.to_string()).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(seq: u32, lines: u64) -> SegmentEntry {
+        SegmentEntry {
+            seq,
+            path: format!("segments/session-{seq:06}.jsonl.gz"),
+            first_ts: 0,
+            last_ts: 0,
+            lines,
+            bytes_uncompressed: 0,
+            bytes_gzip: 0,
+            ..SegmentEntry::default()
+        }
+    }
+
+    #[test]
+    fn backward_window_only_touches_trailing_segments() {
+        let owned: Vec<_> = (1..=200).map(|seq| segment(seq, 10)).collect();
+        let segments: Vec<_> = owned.iter().collect();
+        let window = plan_window(&segments, (200, 9), ReplayDirection::Backward, 15);
+        assert_eq!(
+            window.slices,
+            vec![
+                WindowSlice {
+                    segment: 198,
+                    start: 5,
+                    end: 10
+                },
+                WindowSlice {
+                    segment: 199,
+                    start: 0,
+                    end: 10
+                },
+            ]
+        );
+        assert_eq!(window.prev, Some((198, 4)));
+        assert_eq!(window.next, None);
+    }
+
+    #[test]
+    fn forward_window_continues_from_cursor() {
+        let owned = [segment(1, 3), segment(2, 3), segment(4, 3)];
+        let segments: Vec<_> = owned.iter().collect();
+        let window = plan_window(&segments, (1, 2), ReplayDirection::Forward, 4);
+        assert_eq!(
+            window.slices,
+            vec![
+                WindowSlice {
+                    segment: 0,
+                    start: 2,
+                    end: 3
+                },
+                WindowSlice {
+                    segment: 1,
+                    start: 0,
+                    end: 3
+                },
+            ]
+        );
+        assert_eq!(window.prev, Some((0, 1)));
+        assert_eq!(window.next, Some((2, 0)));
+    }
+
+    #[test]
+    fn anchor_past_the_end_clamps_to_last_line() {
+        let owned = [segment(1, 3), segment(2, 2)];
+        let segments: Vec<_> = owned.iter().collect();
+        let window = plan_window(&segments, (9, 0), ReplayDirection::Backward, 100);
+        assert_eq!(window.slices.len(), 2);
+        assert_eq!(window.prev, None);
+        let window = plan_window(&segments, (9, 0), ReplayDirection::Forward, 100);
+        assert!(window.slices.is_empty());
+        assert_eq!(window.prev, Some((1, 1)));
+    }
+}
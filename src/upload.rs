@@ -1,5 +1,5 @@
 use crate::config::{UploadConfig, WatchConfig};
-use crate::spool::SpoolEntry;
+use crate::spool::{LocalMirror, SpoolEntry, SpoolLayout};
 use anyhow::Result;
 use reqwest::header::{self, HeaderMap, HeaderValue};
 use reqwest::{Client, Method, StatusCode};
@@ -18,6 +18,8 @@ const MAX_DELAY_MS: u64 = 30_000;
 pub struct UploadClient {
     pub client: Client,
     pub config: Arc<WatchConfig>,
+    /// Where dry-run uploads are kept for the dashboard; unset when the UI is off.
+    pub mirror: Option<Arc<LocalMirror>>,
 }
 
 #[derive(Debug, Clone)]
@@ -56,7 +58,20 @@ impl UploadClient {
             .user_agent("agent-uploader/0.1")
             .pool_max_idle_per_host(12)
             .build()?;
-        Ok(Self { client, config })
+        let mirror = (matches!(config.upload, UploadConfig::DryRun)
+            && config.ui.enabled
+            && config.ui.mirror_bytes > 0)
+            .then(|| {
+                Arc::new(LocalMirror::new(
+                    SpoolLayout::from_config(&config),
+                    config.ui.mirror_bytes,
+                ))
+            });
+        Ok(Self {
+            client,
+            config,
+            mirror,
+        })
     }
 
     pub async fn upload(&self, request: UploadRequest) -> Result<()> {
@@ -66,6 +81,11 @@ impl UploadClient {
                     object = tracing::field::display(&request.object_path),
                     "dry-run: skipping upload"
                 );
+                if let Some(mirror) = &self.mirror {
+                    mirror
+                        .keep(&request.object_path, &request.local_path)
+                        .await?;
+                }
                 return Ok(());
             }
             _ => {}
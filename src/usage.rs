@@ -0,0 +1,464 @@
+use crate::config::UsageConfig;
+use crate::manifest::Manifest;
+use crate::rollout::{EventMsg, RolloutItem, TokenCount};
+use crate::spool::SpoolLayout;
+use crate::storage::{LocalInspector, SessionQuery, SessionSort, SessionStore, StorageInspector};
+use crate::tail::SessionEvent;
+use crate::util::load_json_rules;
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// Model name recorded for usage seen before any `turn_context` named one.
+pub const UNKNOWN_MODEL: &str = "unknown";
+const MANIFEST_FETCH_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub cached_input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub reasoning_output_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.cached_input_tokens += other.cached_input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.reasoning_output_tokens += other.reasoning_output_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+
+    /// Usage accrued between two cumulative readings. A reading below the previous one
+    /// means the counter restarted, so all of it is new.
+    fn since(&self, previous: &TokenUsage) -> TokenUsage {
+        if self.total_tokens < previous.total_tokens {
+            return *self;
+        }
+        TokenUsage {
+            input_tokens: self.input_tokens.saturating_sub(previous.input_tokens),
+            cached_input_tokens: self
+                .cached_input_tokens
+                .saturating_sub(previous.cached_input_tokens),
+            output_tokens: self.output_tokens.saturating_sub(previous.output_tokens),
+            reasoning_output_tokens: self
+                .reasoning_output_tokens
+                .saturating_sub(previous.reasoning_output_tokens),
+            total_tokens: self.total_tokens - previous.total_tokens,
+        }
+    }
+}
+
+/// Sum token usage over a map keyed by model.
+pub fn total_usage<'a>(by_model: impl IntoIterator<Item = &'a TokenUsage>) -> TokenUsage {
+    let mut total = TokenUsage::default();
+    for usage in by_model {
+        total.add(usage);
+    }
+    total
+}
+
+/// Add every model's usage from `other` into `into`.
+pub fn merge_usage(into: &mut BTreeMap<String, TokenUsage>, other: &BTreeMap<String, TokenUsage>) {
+    for (model, usage) in other {
+        into.entry(model.clone()).or_default().add(usage);
+    }
+}
+
+enum Reading {
+    /// Running total for the session (`info.total_token_usage`).
+    Cumulative(TokenUsage),
+    /// Usage of a single turn (`info.last_token_usage`, or the flat fields of older CLIs).
+    Delta(TokenUsage),
+}
+
+impl Reading {
+    fn from_count(count: &TokenCount) -> Option<Self> {
+        let nonempty = |usage: Option<TokenUsage>| usage.filter(|usage| !usage.is_empty());
+        match &count.info {
+            Some(info) => nonempty(info.total_token_usage)
+                .map(Reading::Cumulative)
+                .or_else(|| nonempty(info.last_token_usage).map(Reading::Delta)),
+            None => nonempty(Some(count.flat)).map(Reading::Delta),
+        }
+    }
+}
+
+/// Attributes token usage to the model active when it was reported. Codex repeats its
+/// running total on every `token_count` event, so the tracker counts the growth of that
+/// total rather than summing readings, which keeps repeated events from double counting.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    model: Option<String>,
+    last_total: Option<TokenUsage>,
+    by_model: BTreeMap<String, TokenUsage>,
+}
+
+impl UsageTracker {
+    /// Continue a session whose closed segments already account for `total`.
+    pub fn resume(&mut self, model: Option<String>, total: TokenUsage) {
+        self.model = model;
+        self.last_total = (!total.is_empty()).then_some(total);
+    }
+
+    pub fn observe(&mut self, event: &SessionEvent) {
+        if let Some(model) = event.metadata.as_ref().and_then(|meta| meta.model.clone()) {
+            self.model = Some(model);
+        }
+        // Codex `token_count` events: `{"type":"event_msg","payload":{"type":"token_count",...}}`.
+        if event.event_type.as_deref() != Some("event_msg") {
+            return;
+        }
+        if let Some(json) = &event.json
+            && let RolloutItem::EventMsg(EventMsg::TokenCount(count)) = RolloutItem::from_json(json)
+        {
+            self.record(&count);
+        }
+    }
+
+    /// Switch the model that further usage is attributed to.
+    pub fn set_model(&mut self, model: String) {
+        self.model = Some(model);
+    }
+
+    pub fn record(&mut self, count: &TokenCount) {
+        let usage = match Reading::from_count(count) {
+            Some(Reading::Cumulative(total)) => {
+                let usage = match &self.last_total {
+                    Some(previous) => total.since(previous),
+                    None => total,
+                };
+                self.last_total = Some(total);
+                usage
+            }
+            Some(Reading::Delta(usage)) => usage,
+            None => return,
+        };
+        if usage.is_empty() {
+            return;
+        }
+        let model = self.model.as_deref().unwrap_or(UNKNOWN_MODEL);
+        self.by_model
+            .entry(model.to_string())
+            .or_default()
+            .add(&usage);
+    }
+
+    /// Usage since the last reset (the current segment, or turn), keyed by model.
+    pub fn current(&self) -> &BTreeMap<String, TokenUsage> {
+        &self.by_model
+    }
+
+    /// Start counting a new segment or turn; the model and running total carry over.
+    pub fn reset(&mut self) {
+        self.by_model.clear();
+    }
+}
+
+/// Per-model prices in USD per million tokens, loaded from a JSON file such as
+/// `{"gpt-5": {"input": 1.25, "cached_input": 0.125, "output": 10.0}}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pricing {
+    models: BTreeMap<String, ModelPricing>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input: f64,
+    /// Defaults to the full input price.
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+    pub output: f64,
+}
+
+impl Pricing {
+    pub fn load(path: &Path) -> Result<Self> {
+        load_json_rules(path, "pricing file")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Price for `model`: an exact entry, else the longest entry the name starts with,
+    /// so `gpt-5` also prices `gpt-5-codex`.
+    pub fn for_model(&self, model: &str) -> Option<&ModelPricing> {
+        self.models.get(model).or_else(|| {
+            self.models
+                .iter()
+                .filter(|(name, _)| model.starts_with(name.as_str()))
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, pricing)| pricing)
+        })
+    }
+
+    /// Estimated cost of `by_model`, or `None` if any model with usage has no price.
+    pub fn cost(&self, by_model: &BTreeMap<String, TokenUsage>) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        by_model
+            .iter()
+            .filter(|(_, usage)| !usage.is_empty())
+            .map(|(model, usage)| self.for_model(model).map(|price| price.cost(usage)))
+            .sum()
+    }
+}
+
+impl ModelPricing {
+    /// Cached input is billed at its own rate; reasoning tokens are part of `output_tokens`.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_input_tokens.min(usage.input_tokens);
+        let uncached = usage.input_tokens - cached;
+        (uncached as f64 * self.input
+            + cached as f64 * self.cached_input.unwrap_or(self.input)
+            + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Token usage and estimated cost of a session, overall and between checkpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub sid: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    pub tokens: TokenUsage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    pub models: BTreeMap<String, ModelUsage>,
+    pub checkpoints: Vec<CheckpointUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelUsage {
+    #[serde(flatten)]
+    pub tokens: TokenUsage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+/// Usage of the segments closed after the previous checkpoint, up to and including
+/// the segment holding this one. When several checkpoints fall in one segment (rules
+/// with `rotate: false`), the first is attributed the whole segment.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointUsage {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub seq: u32,
+    pub tokens: TokenUsage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl UsageReport {
+    pub fn new(manifest: &Manifest, pricing: &Pricing) -> Self {
+        let by_model = &manifest.stats.tokens;
+        let models = by_model
+            .iter()
+            .map(|(model, usage)| {
+                let cost_usd = pricing.for_model(model).map(|price| price.cost(usage));
+                (
+                    model.clone(),
+                    ModelUsage {
+                        tokens: *usage,
+                        cost_usd,
+                    },
+                )
+            })
+            .collect();
+
+        let mut checkpoints = Vec::with_capacity(manifest.checkpoints.len());
+        let mut after_seq = 0;
+        for checkpoint in &manifest.checkpoints {
+            let mut span = BTreeMap::new();
+            for segment in manifest
+                .segments
+                .iter()
+                .filter(|seg| seg.seq > after_seq && seg.seq <= checkpoint.seq)
+            {
+                merge_usage(&mut span, &segment.tokens);
+            }
+            checkpoints.push(CheckpointUsage {
+                id: checkpoint.id.clone(),
+                label: checkpoint.label.clone(),
+                seq: checkpoint.seq,
+                tokens: total_usage(span.values()),
+                cost_usd: pricing.cost(&span),
+            });
+            after_seq = checkpoint.seq;
+        }
+
+        Self {
+            sid: manifest.sid.clone(),
+            updated_at: manifest.updated_at,
+            tokens: total_usage(by_model.values()),
+            cost_usd: pricing.cost(by_model),
+            models,
+            checkpoints,
+        }
+    }
+}
+
+/// Reports for `agent-uploader usage`: the requested sessions, or every session updated
+/// since `--since`, most recently updated first.
+pub async fn run(config: &UsageConfig) -> Result<Vec<UsageReport>> {
+    let store = match &config.supabase {
+        Some((base_url, api_key)) => SessionStore::Remote(
+            StorageInspector::new(base_url.clone(), api_key.clone(), config.bucket.clone())?
+                .with_keys(config.keyring.clone()),
+        ),
+        None => SessionStore::Local(
+            LocalInspector::new(
+                config.manifest_state_dir.clone(),
+                SpoolLayout::new(config.spool_dir.clone()),
+            )
+            .with_keys(config.keyring.clone()),
+        ),
+    };
+    let sids = if config.sids.is_empty() {
+        let query = SessionQuery {
+            sort: SessionSort::UpdatedAt,
+            descending: true,
+            ..SessionQuery::default()
+        };
+        store
+            .list_sessions(&config.root_prefix, &query)
+            .await?
+            .sessions
+            .into_iter()
+            .filter(|session| config.since.is_none_or(|since| session.updated_at >= since))
+            .map(|session| session.sid)
+            .collect()
+    } else {
+        config.sids.clone()
+    };
+    let store = &store;
+    stream::iter(sids)
+        .map(|sid| async move {
+            let manifest = store.fetch_manifest(&config.root_prefix, &sid).await?;
+            Ok::<_, anyhow::Error>(UsageReport::new(&manifest, &config.pricing))
+        })
+        .buffered(MANIFEST_FETCH_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// Plain-text table of usage reports with a total row.
+pub struct UsageTable<'a> {
+    pub reports: &'a [UsageReport],
+    pub checkpoints: bool,
+}
+
+impl fmt::Display for UsageTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter<'_>,
+                   name: &str,
+                   when: &str,
+                   tokens: &TokenUsage,
+                   cost: Option<f64>| {
+            writeln!(
+                f,
+                "{:<40} {:<16} {:>12} {:>12} {:>12} {:>10}",
+                name,
+                when,
+                tokens.input_tokens,
+                tokens.cached_input_tokens,
+                tokens.output_tokens,
+                cost.map_or_else(|| "-".to_string(), |cost| format!("${cost:.2}"))
+            )
+        };
+        writeln!(
+            f,
+            "{:<40} {:<16} {:>12} {:>12} {:>12} {:>10}",
+            "SESSION", "UPDATED", "INPUT", "CACHED", "OUTPUT", "COST"
+        )?;
+        let mut total = TokenUsage::default();
+        let mut total_cost = Some(0.0);
+        for report in self.reports {
+            let updated = report
+                .updated_at
+                .format(format_description!("[year]-[month]-[day] [hour]:[minute]"))
+                .unwrap_or_default();
+            row(f, &report.sid, &updated, &report.tokens, report.cost_usd)?;
+            if self.checkpoints {
+                for checkpoint in &report.checkpoints {
+                    let name = format!("  {}", checkpoint.label.as_ref().unwrap_or(&checkpoint.id));
+                    row(f, &name, "", &checkpoint.tokens, checkpoint.cost_usd)?;
+                }
+            }
+            total.add(&report.tokens);
+            total_cost = match (total_cost, report.cost_usd) {
+                (Some(sum), Some(cost)) => Some(sum + cost),
+                _ => None,
+            };
+        }
+        let sessions = format!("TOTAL ({} sessions)", self.reports.len());
+        row(f, &sessions, "", &total, total_cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(line: &str) -> SessionEvent {
+        SessionEvent::from_line(line.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn repeated_totals_are_counted_once() {
+        let mut tracker = UsageTracker::default();
+        tracker.observe(&event(
+            r#"{"type":"turn_context","payload":{"model":"gpt-5"}}"#,
+        ));
+        let count = r#"{"type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":100,"cached_input_tokens":40,"output_tokens":10,"total_tokens":110}}}}"#;
+        tracker.observe(&event(count));
+        tracker.observe(&event(count));
+        tracker.observe(&event(
+            r#"{"type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":250,"cached_input_tokens":100,"output_tokens":30,"total_tokens":280}}}}"#,
+        ));
+        let usage = tracker.current()["gpt-5"];
+        assert_eq!(usage.input_tokens, 250);
+        assert_eq!(usage.cached_input_tokens, 100);
+        assert_eq!(usage.output_tokens, 30);
+    }
+
+    #[test]
+    fn pricing_falls_back_to_longest_prefix() {
+        let pricing: Pricing = serde_json::from_str(
+            r#"{"gpt-5": {"input": 1.0, "output": 10.0}, "gpt-5-mini": {"input": 0.5, "cached_input": 0.1, "output": 2.0}}"#,
+        )
+        .unwrap();
+        assert_eq!(pricing.for_model("gpt-5-codex").unwrap().input, 1.0);
+        assert_eq!(pricing.for_model("gpt-5-mini-2025").unwrap().input, 0.5);
+        assert!(pricing.for_model("o3").is_none());
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            cached_input_tokens: 500_000,
+            output_tokens: 100_000,
+            ..TokenUsage::default()
+        };
+        let cost = pricing.for_model("gpt-5-mini").unwrap().cost(&usage);
+        assert!((cost - (0.25 + 0.05 + 0.2)).abs() < 1e-9);
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result, bail};
 use path_absolutize::Absolutize;
+use serde::de::DeserializeOwned;
 use std::path::{Path, PathBuf};
 use time::OffsetDateTime;
 use time::format_description::FormatItem;
@@ -19,7 +20,7 @@ pub fn expand_path(path: &Path) -> Result<PathBuf> {
             .context("unable to resolve home directory for path expansion")?
             .home_dir()
             .to_path_buf();
-        let stripped = stripped.trim_start_matches(|c| c == '/' || c == '\\');
+        let stripped = stripped.trim_start_matches(['/', '\\']);
         if stripped.is_empty() {
             base
         } else {
@@ -34,6 +35,21 @@ pub fn expand_path(path: &Path) -> Result<PathBuf> {
         .context("failed to absolutize path")
 }
 
+/// Read a JSON rules or settings file named `what` in error messages.
+pub fn load_json_rules<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read {what} {}", path.display()))?;
+    serde_json::from_slice(&bytes).with_context(|| format!("invalid {what} {}", path.display()))
+}
+
+/// Reject JSON pointers that `Value::pointer` could never resolve.
+pub fn validate_pointer(pointer: &str) -> Result<()> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        bail!("JSON pointer {pointer:?} must be empty or start with '/'");
+    }
+    Ok(())
+}
+
 pub fn generate_sid() -> String {
     let ts = OffsetDateTime::now_utc()
         .format(SID_FORMAT)
@@ -54,3 +70,44 @@ pub fn ensure_dir(path: &Path) -> Result<()> {
         .with_context(|| format!("failed to create directory {}", path.display()))?;
     Ok(())
 }
+
+/// Best-effort liveness probe for `--pid`; unknown platforms report the process as alive.
+pub fn process_alive(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        // Pids that do not fit a positive pid_t would address process groups.
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        if pid <= 0 {
+            return false;
+        }
+        // Signal 0 only checks the target; EPERM means it exists under another user.
+        let result = unsafe { libc::kill(pid, 0) };
+        result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn process_alive_tracks_exit() {
+        assert!(process_alive(std::process::id()));
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!process_alive(pid));
+    }
+}
@@ -1,63 +1,82 @@
+use crate::attachments::{ATTACHMENTS_DIR, AttachmentEntry};
+use crate::config::{SessionDirConfig, sid_for_session_file};
+use crate::crypto::{Keyring, SEALED_SUFFIX};
+use crate::format::{self, SessionFormat};
+use crate::heartbeat::{HEARTBEAT_FILENAME, Heartbeat, local_hostname, local_username};
+use crate::index::{INDEX_DIR, IndexWriter, SessionIndex};
+use crate::live::{LiveHub, LiveLine};
 use crate::manifest::{Manifest, ManifestStore};
 use crate::segment::{PendingCheckpoint, SegmentClosed, SegmentWriter};
 use crate::spool::{SpoolItemKind, SpoolLayout, SpoolMetadata, SpoolQueue};
-use crate::tail::{TailBatch, TailReader};
-use crate::upload::UploadClient;
-use crate::util::ensure_dir;
+use crate::tail::{EventParser, SessionEvent, TailBatch, TailReader};
+use crate::upload::{UploadClient, UploadRequest};
+use crate::util::{ensure_dir, process_alive};
 use crate::{Result, WatchConfig};
 use futures::stream::{self, StreamExt, TryStreamExt};
-use std::path::Path;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use time::OffsetDateTime;
 use tokio::signal;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
-pub async fn run(config: Arc<WatchConfig>) -> Result<()> {
+const SESSION_FILE_EXTENSION: &str = "jsonl";
+
+/// Tail the configured file or directory until shutdown. When `live` is set, every tailed
+/// line is also published to it for the UI's streaming endpoint.
+pub async fn run(config: Arc<WatchConfig>, live: Option<Arc<LiveHub>>) -> Result<()> {
     let spool_layout = SpoolLayout::from_config(&config);
     spool_layout.ensure()?;
     ensure_dir(&config.manifest_state_dir)?;
 
-    let manifest_state_path = config
-        .manifest_state_dir
-        .join(format!("{}.json", config.sid));
-    let manifest_store = ManifestStore::new(manifest_state_path);
-    let mut manifest = manifest_store.load_or_new(&config)?;
-
-    let starting_seq = manifest.active_seq;
-    let mut tail_reader = TailReader::new(config.session_file.clone()).await?;
-    let mut segment_writer =
-        SegmentWriter::new(config.clone(), spool_layout.clone(), starting_seq).await?;
-    let spool_queue = Arc::new(SpoolQueue::new(spool_layout.clone()));
-    let uploader = Arc::new(UploadClient::new(config.clone())?);
-    let manifest_remote_path = Manifest::manifest_path(&config.object_prefix());
-    let manifest_upload_path = spool_layout.queue_manifest_path();
-    let concurrency = config.concurrency.max(1);
-
-    if let Err(err) = drain_spool(spool_queue.clone(), uploader.clone(), concurrency).await {
+    let sink = SpoolSink::new(&config, &spool_layout)?;
+    if let Err(err) = sink.drain().await {
         tracing::warn!(error = %err, "failed to drain existing spool entries at startup");
     }
 
+    match config.session_dir.clone() {
+        Some(dir) => run_directory(config, dir, &spool_layout, &sink, live).await,
+        None => run_file(config, &spool_layout, &sink, live).await,
+    }
+}
+
+async fn run_file(
+    config: Arc<WatchConfig>,
+    spool_layout: &SpoolLayout,
+    sink: &SpoolSink,
+    live: Option<Arc<LiveHub>>,
+) -> Result<()> {
+    let offset = file_start_offset(&config);
+    let mut session = SessionWatcher::open(config.clone(), spool_layout, offset, live).await?;
+    if config.once {
+        return run_once(&mut session, sink).await;
+    }
     let mut interval = crate::tail::poll_interval(config.poll_interval);
 
     loop {
         tokio::select! {
             _ = signal::ctrl_c() => {
                 tracing::info!("shutdown signal received");
-                finalize(&mut segment_writer, &mut manifest, &manifest_store, &spool_queue, &manifest_upload_path, &manifest_remote_path, &uploader, concurrency).await?;
+                session.finalize(sink).await?;
+                if let Err(err) = sink.drain().await {
+                    tracing::warn!(error = %err, "failed to upload all queued items during shutdown");
+                }
                 break;
             }
             _ = interval.tick() => {
-                if let Some(batch) = tail_reader.poll().await? {
-                    handle_batch(
-                        batch,
-                        &mut segment_writer,
-                        &mut manifest,
-                        &manifest_store,
-                        &spool_queue,
-                        &manifest_upload_path,
-                        &manifest_remote_path,
-                        &uploader,
-                        concurrency,
-                    ).await?;
+                session.poll(sink).await?;
+                session.maybe_preview(sink).await;
+                session.maybe_heartbeat(sink).await;
+                if let Some(reason) = session.end_reason() {
+                    tracing::info!(sid = %config.sid, reason, "session ended");
+                    session.complete(sink).await?;
+                    if let Err(err) = sink.drain().await {
+                        tracing::warn!(error = %err, "failed to upload all queued items after session end");
+                    }
+                    break;
                 }
             }
         }
@@ -66,173 +85,824 @@ pub async fn run(config: Arc<WatchConfig>) -> Result<()> {
     Ok(())
 }
 
-async fn handle_batch(
-    batch: TailBatch,
-    segment_writer: &mut SegmentWriter,
-    manifest: &mut Manifest,
-    manifest_store: &ManifestStore,
-    spool_queue: &Arc<SpoolQueue>,
-    manifest_upload_path: &Path,
-    manifest_remote_path: &str,
-    uploader: &Arc<UploadClient>,
-    concurrency: usize,
+/// Upload everything currently in the file and fail if the spool could not be emptied,
+/// so headless callers can tell a complete upload from a partial one.
+async fn run_once(session: &mut SessionWatcher, sink: &SpoolSink) -> Result<()> {
+    session.poll(sink).await?;
+    session.flush_remainder(sink).await?;
+    session.finalize(sink).await?;
+    if let Err(err) = sink.drain().await {
+        tracing::warn!(error = %err, "failed to upload all queued items");
+    }
+    let pending = sink.pending().await?;
+    if pending > 0 {
+        anyhow::bail!("{pending} items remain queued in the spool after upload");
+    }
+    tracing::info!(sid = %session.config.sid, "uploaded session file");
+    Ok(())
+}
+
+pub(crate) struct DiscoveredFile {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+async fn run_directory(
+    config: Arc<WatchConfig>,
+    dir: SessionDirConfig,
+    spool_layout: &SpoolLayout,
+    sink: &SpoolSink,
+    live: Option<Arc<LiveHub>>,
 ) -> Result<()> {
-    if batch.truncated {
-        if let Some(closed) = segment_writer.force_rotate().await? {
-            finalize_segment(
-                closed,
-                manifest,
-                manifest_store,
-                spool_queue,
-                manifest_upload_path,
-                manifest_remote_path,
-                uploader,
-                concurrency,
-            )
-            .await?;
+    tracing::info!(dir = %dir.root.display(), "watching session directory");
+    let mut attached: HashMap<PathBuf, SessionWatcher> = HashMap::new();
+    // Offsets of files that are not attached: detached for idleness, or uploaded by an
+    // earlier run according to their manifest state. A file that grows resumes where it
+    // left off instead of re-uploading from the start.
+    let mut detached: HashMap<PathBuf, u64> = HashMap::new();
+    let mut poll = crate::tail::poll_interval(config.poll_interval);
+    let mut scan = crate::tail::poll_interval(dir.scan_interval);
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                tracing::info!("shutdown signal received");
+                for (path, session) in attached.iter_mut() {
+                    if let Err(err) = session.finalize(sink).await {
+                        tracing::warn!(file = %path.display(), error = %err, "failed to finalize session");
+                    }
+                }
+                if let Err(err) = sink.drain().await {
+                    tracing::warn!(error = %err, "failed to upload all queued items during shutdown");
+                }
+                break;
+            }
+            _ = scan.tick() => {
+                for file in discover_session_files(&dir.root) {
+                    if attached.contains_key(&file.path) {
+                        continue;
+                    }
+                    if !detached.contains_key(&file.path)
+                        && let Ok(sid) = sid_for_session_file(&file.path)
+                        && let Some(offset) = stored_offset(&config, &sid)
+                    {
+                        detached.insert(file.path.clone(), offset);
+                    }
+                    let offset = match detached.get(&file.path) {
+                        Some(offset) if file.len > *offset => *offset,
+                        // Truncated or replaced by a shorter file: start it over.
+                        Some(offset) if file.len < *offset => 0,
+                        Some(_) => continue,
+                        None if is_recent(file.modified, dir.idle_detach) => 0,
+                        None => continue,
+                    };
+                    match attach_session(&config, spool_layout, &file.path, offset, live.clone()).await {
+                        Ok(watcher) => {
+                            tracing::info!(
+                                file = %file.path.display(),
+                                sid = %watcher.config.sid,
+                                offset,
+                                "attached session file"
+                            );
+                            detached.remove(&file.path);
+                            attached.insert(file.path, watcher);
+                        }
+                        Err(err) => {
+                            tracing::warn!(file = %file.path.display(), error = %err, "failed to attach session file");
+                        }
+                    }
+                }
+            }
+            _ = poll.tick() => {
+                let mut to_detach = Vec::new();
+                for (path, session) in attached.iter_mut() {
+                    match session.poll(sink).await {
+                        Ok(_) => {
+                            session.maybe_preview(sink).await;
+                            session.maybe_heartbeat(sink).await;
+                            if let Some(reason) = session.end_reason() {
+                                to_detach.push((path.clone(), Some(reason)));
+                            } else if session.idle_for() >= dir.idle_detach {
+                                to_detach.push((path.clone(), None));
+                            }
+                        }
+                        Err(err) => {
+                            tracing::warn!(file = %path.display(), error = %err, "failed to tail session file; detaching");
+                            to_detach.push((path.clone(), None));
+                        }
+                    }
+                }
+                for (path, end_reason) in to_detach {
+                    let Some(mut session) = attached.remove(&path) else {
+                        continue;
+                    };
+                    let result = match end_reason {
+                        Some(reason) => {
+                            tracing::info!(sid = %session.config.sid, reason, "session ended");
+                            session.complete(sink).await
+                        }
+                        None => session.finalize(sink).await,
+                    };
+                    if let Err(err) = result {
+                        tracing::warn!(file = %path.display(), error = %err, "failed to finalize session");
+                    }
+                    tracing::info!(file = %path.display(), sid = %session.config.sid, "detached session file");
+                    detached.insert(path, session.tail_reader.consumed_offset());
+                }
+            }
         }
     }
 
-    for event in batch.events {
-        if let Some(closed) = segment_writer.append(&event).await? {
-            finalize_segment(
-                closed,
-                manifest,
-                manifest_store,
-                spool_queue,
-                manifest_upload_path,
-                manifest_remote_path,
-                uploader,
-                concurrency,
-            )
-            .await?;
+    Ok(())
+}
+
+async fn attach_session(
+    config: &WatchConfig,
+    spool_layout: &SpoolLayout,
+    path: &Path,
+    offset: u64,
+    live: Option<Arc<LiveHub>>,
+) -> Result<SessionWatcher> {
+    let session_config = Arc::new(config.for_session_file(path)?);
+    SessionWatcher::open(session_config, spool_layout, offset, live).await
+}
+
+/// Where an earlier run stopped reading `path`, from the local manifest state of its sid.
+/// Where `--file` mode resumes: the offset an earlier run recorded, unless the file is
+/// now shorter than that and has to be read again from the start.
+fn file_start_offset(config: &WatchConfig) -> u64 {
+    let len = std::fs::metadata(&config.session_file).map_or(0, |meta| meta.len());
+    match stored_offset(config, &config.sid) {
+        Some(offset) if offset <= len => offset,
+        _ => 0,
+    }
+}
+
+fn stored_offset(config: &WatchConfig, sid: &str) -> Option<u64> {
+    let state_path = config.manifest_state_dir.join(format!("{sid}.json"));
+    if !state_path.exists() {
+        return None;
+    }
+    match Manifest::load_or_new(&state_path, config) {
+        Ok(manifest) => (manifest.source_offset > 0).then_some(manifest.source_offset),
+        Err(err) => {
+            tracing::warn!(sid, error = %err, "failed to read manifest state");
+            None
         }
     }
-    Ok(())
 }
 
-async fn finalize(
-    segment_writer: &mut SegmentWriter,
-    manifest: &mut Manifest,
-    manifest_store: &ManifestStore,
-    spool_queue: &Arc<SpoolQueue>,
-    manifest_upload_path: &Path,
-    manifest_remote_path: &str,
-    uploader: &Arc<UploadClient>,
-    concurrency: usize,
-) -> Result<()> {
-    if let Some(closed) = segment_writer.force_rotate().await? {
-        finalize_segment(
-            closed,
-            manifest,
-            manifest_store,
-            spool_queue,
-            manifest_upload_path,
-            manifest_remote_path,
-            uploader,
-            concurrency,
+pub(crate) fn is_recent(modified: SystemTime, window: Duration) -> bool {
+    match SystemTime::now().duration_since(modified) {
+        Ok(age) => age < window,
+        Err(_) => true,
+    }
+}
+
+pub(crate) fn discover_session_files(root: &Path) -> Vec<DiscoveredFile> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                tracing::debug!(dir = %dir.display(), error = %err, "failed to scan directory");
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SESSION_FILE_EXTENSION) {
+                continue;
+            }
+            let Ok(modified) = meta.modified() else {
+                continue;
+            };
+            found.push(DiscoveredFile {
+                path,
+                len: meta.len(),
+                modified,
+            });
+        }
+    }
+    found
+}
+
+/// Tails one session file and feeds its segments, checkpoints and manifest into the spool.
+pub(crate) struct SessionWatcher {
+    config: Arc<WatchConfig>,
+    spool_layout: SpoolLayout,
+    tail_reader: TailReader,
+    segment_writer: SegmentWriter,
+    manifest: Manifest,
+    manifest_store: ManifestStore,
+    manifest_upload_path: PathBuf,
+    manifest_remote_path: String,
+    last_activity: Instant,
+    last_event_at: Option<OffsetDateTime>,
+    end_event_seen: bool,
+    heartbeat_local_path: PathBuf,
+    heartbeat_remote_path: String,
+    last_heartbeat: Option<Instant>,
+    heartbeat_upload: Option<JoinHandle<()>>,
+    last_preview: Option<Instant>,
+    previewed: Option<(u32, u64)>,
+    live: Option<Arc<LiveHub>>,
+}
+
+impl SessionWatcher {
+    pub(crate) async fn open(
+        config: Arc<WatchConfig>,
+        spool_root: &SpoolLayout,
+        offset: u64,
+        live: Option<Arc<LiveHub>>,
+    ) -> Result<Self> {
+        let spool_layout = spool_root.for_session(&config.sid);
+        spool_layout.ensure()?;
+
+        let manifest_state_path = config
+            .manifest_state_dir
+            .join(format!("{}.json", config.sid));
+        let manifest_store = ManifestStore::new(manifest_state_path);
+        let mut manifest = manifest_store.load_or_new(&config)?;
+        manifest
+            .metadata
+            .set_origin(local_hostname(), local_username());
+
+        let starting_seq = manifest.active_seq;
+        // `--format auto` goes by the file's head, not whichever line tailing resumes at.
+        let detected = match manifest.format {
+            Some(format) => format.adapter(),
+            None if config.format == SessionFormat::Auto => {
+                format::detect_file(&config.session_file)?
+            }
+            None => None,
+        };
+        let tail_reader = TailReader::open_at(
+            config.session_file.clone(),
+            offset,
+            EventParser::from_config(&config).with_detected(detected),
         )
         .await?;
-    } else {
-        queue_manifest(
+        let mut segment_writer =
+            SegmentWriter::new(config.clone(), spool_layout.clone(), starting_seq).await?;
+        // Codex totals are cumulative. Reading from the start sees every reading again,
+        // so only a mid-file resume continues from the total already recorded.
+        if offset > 0 {
+            segment_writer.resume_usage(
+                manifest.metadata.model.clone(),
+                manifest.stats.total_tokens(),
+            );
+        }
+        let manifest_remote_path = Manifest::manifest_path(&config.object_prefix());
+        let manifest_upload_path = spool_layout.queue_manifest_path();
+        let heartbeat_local_path = spool_layout.active_dir.join(HEARTBEAT_FILENAME);
+        let heartbeat_remote_path = Heartbeat::remote_path(&config.object_prefix());
+        if let Some(live) = &live {
+            live.open(&config.sid);
+        }
+
+        Ok(Self {
+            config,
+            spool_layout,
+            tail_reader,
+            segment_writer,
             manifest,
             manifest_store,
-            spool_queue,
             manifest_upload_path,
             manifest_remote_path,
-        )
-        .await?;
+            last_activity: Instant::now(),
+            last_event_at: None,
+            end_event_seen: false,
+            heartbeat_local_path,
+            heartbeat_remote_path,
+            last_heartbeat: None,
+            heartbeat_upload: None,
+            last_preview: None,
+            previewed: None,
+            live,
+        })
     }
 
-    if let Err(err) = drain_spool(spool_queue.clone(), uploader.clone(), concurrency).await {
-        tracing::warn!(error = %err, "failed to upload all queued items during shutdown");
+    pub(crate) fn manifest(&self) -> &Manifest {
+        &self.manifest
     }
 
-    Ok(())
+    /// Poll the session file once; returns whether any new data was consumed.
+    pub(crate) async fn poll(&mut self, sink: &SpoolSink) -> Result<bool> {
+        match self.tail_reader.poll().await? {
+            Some(batch) => {
+                self.last_activity = Instant::now();
+                self.handle_batch(batch, sink).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Publish the active segment as a preview once per preview interval, if it changed.
+    pub(crate) async fn maybe_preview(&mut self, sink: &SpoolSink) {
+        let Some(interval) = self.config.preview else {
+            return;
+        };
+        if self.last_preview.is_some_and(|at| at.elapsed() < interval) {
+            return;
+        }
+        self.last_preview = Some(Instant::now());
+        let position = (
+            self.segment_writer.seq(),
+            self.segment_writer.buffered_lines(),
+        );
+        if position.1 == 0 || self.previewed == Some(position) {
+            return;
+        }
+        if let Err(err) = self.queue_preview(sink).await {
+            tracing::warn!(sid = %self.config.sid, error = %err, "failed to queue segment preview");
+            return;
+        }
+        self.previewed = Some(position);
+        if let Err(err) = sink.drain().await {
+            tracing::warn!(error = %err, "preview upload failed; data will remain in spool");
+        }
+    }
+
+    async fn queue_preview(&mut self, sink: &SpoolSink) -> Result<()> {
+        let Some(preview) = self.segment_writer.preview().await? else {
+            return Ok(());
+        };
+        let metadata = segment_spool_metadata(
+            preview.remote_path.clone(),
+            preview.content_encoding.clone(),
+            SpoolItemKind::Preview,
+        );
+        sink.queue.enqueue(&preview.local_path, &metadata).await?;
+        self.manifest.set_preview(preview.entry);
+        self.queue_manifest(sink).await
+    }
+
+    /// Refresh `live.json` once per heartbeat interval. The upload runs in the background so
+    /// a slow or offline Storage endpoint never stalls tailing; failures are only logged.
+    pub(crate) async fn maybe_heartbeat(&mut self, sink: &SpoolSink) {
+        let Some(interval) = self.config.heartbeat else {
+            return;
+        };
+        if self
+            .last_heartbeat
+            .is_some_and(|at| at.elapsed() < interval)
+            || self
+                .heartbeat_upload
+                .as_ref()
+                .is_some_and(|task| !task.is_finished())
+        {
+            return;
+        }
+        self.last_heartbeat = Some(Instant::now());
+        if let Err(err) = self.send_heartbeat(sink).await {
+            tracing::debug!(sid = %self.config.sid, error = %err, "failed to write heartbeat");
+        }
+    }
+
+    async fn send_heartbeat(&mut self, sink: &SpoolSink) -> Result<()> {
+        let heartbeat = Heartbeat {
+            sid: self.config.sid.clone(),
+            host: local_hostname(),
+            pid: std::process::id(),
+            updated_at: OffsetDateTime::now_utc(),
+            active_seq: self.segment_writer.seq(),
+            buffered_lines: self.segment_writer.buffered_lines(),
+            spool_depth: sink.pending_for(&self.config.object_prefix()).await?,
+            last_upload_error: sink.last_error(),
+        };
+        let mut bytes = heartbeat.to_bytes()?;
+        if let Some(keys) = &self.config.keyring {
+            bytes = keys.seal_json(&self.heartbeat_remote_path, &bytes)?;
+        }
+        tokio::fs::write(&self.heartbeat_local_path, &bytes).await?;
+        if self.config.dry_run {
+            return Ok(());
+        }
+        let request = UploadRequest {
+            object_path: self.heartbeat_remote_path.clone(),
+            local_path: self.heartbeat_local_path.clone(),
+            content_type: Some("application/json".to_string()),
+            content_encoding: None,
+        };
+        let uploader = sink.uploader.clone();
+        self.heartbeat_upload = Some(tokio::spawn(async move {
+            if let Err(err) = uploader.upload(request).await {
+                tracing::debug!(error = %err, "heartbeat upload failed");
+            }
+        }));
+        Ok(())
+    }
+
+    pub(crate) fn idle_for(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// Whether any configured end-of-session signal has fired.
+    pub(crate) fn end_reason(&self) -> Option<&'static str> {
+        let end = &self.config.session_end;
+        if self.end_event_seen {
+            return Some("end event");
+        }
+        if let Some(idle) = end.idle
+            && self.idle_for() >= idle
+        {
+            return Some("idle timeout");
+        }
+        if let Some(pid) = end.pid
+            && !process_alive(pid)
+        {
+            return Some("process exited");
+        }
+        None
+    }
+
+    async fn handle_batch(&mut self, batch: TailBatch, sink: &SpoolSink) -> Result<()> {
+        if batch.truncated
+            && let Some(closed) = self.segment_writer.force_rotate().await?
+        {
+            self.finalize_segment(closed, sink).await?;
+        }
+
+        if let Some(last) = batch.events.last() {
+            self.last_event_at = Some(last.timestamp);
+            self.manifest.reopen();
+        }
+        let mut manifest_changed = false;
+        for event in batch.events {
+            manifest_changed |= self.manifest.metadata.observe(&event);
+            if let Some(event_type) = event.event_type.as_deref()
+                && self
+                    .config
+                    .session_end
+                    .event_types
+                    .iter()
+                    .any(|end| end == event_type)
+            {
+                self.end_event_seen = true;
+            }
+            let Some(mut event) = self.filter(event) else {
+                continue;
+            };
+            manifest_changed |= self.offload(&mut event, sink).await?;
+            self.publish_live(&event);
+            if let Some(closed) = self.segment_writer.append(&event).await? {
+                self.finalize_segment(closed, sink).await?;
+                manifest_changed = false;
+            }
+        }
+        self.record_progress();
+        // Publish metadata (and where new attachments live) as soon as it is known rather
+        // than at the first rotation, so a new session is searchable while its first
+        // segment is still open.
+        if manifest_changed {
+            self.queue_manifest(sink).await?;
+            if sink.drain_on_rotate
+                && let Err(err) = sink.drain().await
+            {
+                tracing::warn!(error = %err, "upload failed; data will remain in spool");
+            }
+        } else {
+            self.manifest_store.save(&self.manifest)?;
+        }
+        Ok(())
+    }
+
+    /// Apply `--event-rules`; dropped events are only counted.
+    fn filter(&mut self, event: SessionEvent) -> Option<SessionEvent> {
+        let Some(filter) = &self.config.event_filter else {
+            return Some(event);
+        };
+        let kept = filter.apply(event);
+        if kept.is_none() {
+            self.segment_writer.record_dropped();
+        }
+        kept
+    }
+
+    /// Move large strings into attachment objects per `--attachment-bytes`, queuing the
+    /// ones this session has not stored yet. Returns whether the manifest gained any.
+    async fn offload(&mut self, event: &mut SessionEvent, sink: &SpoolSink) -> Result<bool> {
+        let Some(policy) = self.config.attachments else {
+            return Ok(false);
+        };
+        let mut added = false;
+        for attachment in policy.offload(event) {
+            if self.manifest.attachments.contains_key(&attachment.hash) {
+                continue;
+            }
+            let entry = self
+                .queue_attachment(attachment.hash.clone(), attachment.body, sink)
+                .await?;
+            self.manifest.add_attachment(attachment.hash, entry);
+            added = true;
+        }
+        Ok(added)
+    }
+
+    async fn queue_attachment(
+        &self,
+        hash: String,
+        body: String,
+        sink: &SpoolSink,
+    ) -> Result<AttachmentEntry> {
+        let original_len = body.len() as u64;
+        let mut name = hash;
+        let mut bytes = body.into_bytes();
+        let compression = self.config.compression;
+        let mut content_encoding = compression.content_encoding().map(str::to_string);
+        if compression.is_enabled() {
+            bytes = compression.compress(&bytes)?;
+            name.push_str(compression.extension());
+        }
+        if self.config.keyring.is_some() {
+            name.push_str(SEALED_SUFFIX);
+            content_encoding = None;
+        }
+        let path = format!("{ATTACHMENTS_DIR}/{name}");
+        let remote_path = format!("{}/{path}", self.config.object_prefix());
+        if let Some(keys) = &self.config.keyring {
+            bytes = keys.seal(&remote_path, &bytes)?;
+        }
+        let local_path = self.spool_layout.queued_attachment_path(&name);
+        tokio::fs::write(&local_path, &bytes).await?;
+        let content_type = if content_encoding.is_some() || name.ends_with(SEALED_SUFFIX) {
+            "application/octet-stream"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+        let metadata = SpoolMetadata {
+            remote_path,
+            content_type: Some(content_type.to_string()),
+            content_encoding,
+            created_at: OffsetDateTime::now_utc(),
+            kind: SpoolItemKind::Attachment,
+        };
+        sink.queue.enqueue(&local_path, &metadata).await?;
+        Ok(AttachmentEntry {
+            path,
+            bytes: original_len,
+        })
+    }
+
+    fn publish_live(&self, event: &SessionEvent) {
+        if let Some(live) = &self.live {
+            let line = LiveLine::new(
+                self.segment_writer.seq(),
+                self.segment_writer.buffered_lines(),
+                event,
+            );
+            live.publish(&self.config.sid, line);
+        }
+    }
+
+    /// Everything read so far is in a segment now, so a restart can resume after it with
+    /// the same format.
+    fn record_progress(&mut self) {
+        self.manifest.source_offset = self.tail_reader.consumed_offset();
+        if let Some(adapter) = self.tail_reader.parser().adapter() {
+            self.manifest.format = Some(SessionFormat::of(adapter));
+        }
+    }
+
+    /// Append a trailing line that lacks its newline; the caller asserts the file is complete.
+    pub(crate) async fn flush_remainder(&mut self, sink: &SpoolSink) -> Result<()> {
+        let Some(event) = self.tail_reader.take_remainder() else {
+            return Ok(());
+        };
+        self.record_progress();
+        self.last_event_at = Some(event.timestamp);
+        let Some(mut event) = self.filter(event) else {
+            return Ok(());
+        };
+        self.offload(&mut event, sink).await?;
+        self.publish_live(&event);
+        if let Some(closed) = self.segment_writer.append(&event).await? {
+            self.finalize_segment(closed, sink).await?;
+        }
+        Ok(())
+    }
+
+    /// Close out a finished session: flush the last segment and mark the manifest complete.
+    pub(crate) async fn complete(&mut self, sink: &SpoolSink) -> Result<()> {
+        self.flush_remainder(sink).await?;
+        let ended_at = self.last_event_at.unwrap_or_else(OffsetDateTime::now_utc);
+        self.manifest.mark_complete(ended_at);
+        self.finalize(sink).await
+    }
+
+    /// Flush the active segment when the watcher stops following the file.
+    pub(crate) async fn finalize(&mut self, sink: &SpoolSink) -> Result<()> {
+        if let Some(live) = &self.live {
+            live.close(&self.config.sid);
+        }
+        if let Some(closed) = self.segment_writer.force_rotate().await? {
+            self.finalize_segment(closed, sink).await
+        } else {
+            self.queue_manifest(sink).await
+        }
+    }
+
+    async fn finalize_segment(&mut self, closed: SegmentClosed, sink: &SpoolSink) -> Result<()> {
+        self.manifest
+            .add_segment(closed.entry.clone(), &closed.stats);
+        for cp in &closed.checkpoints {
+            self.manifest.add_checkpoint(cp.manifest_entry());
+        }
+
+        let segment_metadata = segment_spool_metadata(
+            closed.upload_remote_path.clone(),
+            closed.content_encoding.clone(),
+            SpoolItemKind::Segment,
+        );
+        sink.queue
+            .enqueue(&closed.upload_local_path, &segment_metadata)
+            .await?;
+
+        for cp in &closed.checkpoints {
+            queue_checkpoint(cp, &sink.queue, self.config.keyring.as_ref()).await?;
+        }
+
+        self.queue_manifest(sink).await?;
+
+        if sink.drain_on_rotate
+            && let Err(err) = sink.drain().await
+        {
+            tracing::warn!(error = %err, "upload failed; data will remain in spool");
+        }
+        Ok(())
+    }
+
+    async fn queue_manifest(&self, sink: &SpoolSink) -> Result<()> {
+        self.manifest_store.save(&self.manifest)?;
+        sink.record_manifest(&self.manifest);
+        let mut bytes = self.manifest.to_bytes()?;
+        if let Some(keys) = &self.config.keyring {
+            bytes = keys.seal_json(&self.manifest_remote_path, &bytes)?;
+        }
+        tokio::fs::write(&self.manifest_upload_path, &bytes).await?;
+        let manifest_metadata = SpoolMetadata {
+            remote_path: self.manifest_remote_path.clone(),
+            content_type: Some("application/json".to_string()),
+            content_encoding: None,
+            created_at: OffsetDateTime::now_utc(),
+            kind: SpoolItemKind::Manifest,
+        };
+        sink.queue
+            .enqueue(&self.manifest_upload_path, &manifest_metadata)
+            .await?;
+        Ok(())
+    }
 }
 
-async fn finalize_segment(
-    closed: SegmentClosed,
-    manifest: &mut Manifest,
-    manifest_store: &ManifestStore,
-    spool_queue: &Arc<SpoolQueue>,
-    manifest_upload_path: &Path,
-    manifest_remote_path: &str,
-    uploader: &Arc<UploadClient>,
+/// Upload side shared by every watched session: one spool queue drained by one client.
+pub(crate) struct SpoolSink {
+    queue: Arc<SpoolQueue>,
+    uploader: Arc<UploadClient>,
     concurrency: usize,
-) -> Result<()> {
-    let checkpoint = closed.checkpoint.clone();
-    manifest.add_segment(closed.entry.clone());
-    if let Some(ref cp) = checkpoint {
-        manifest.add_checkpoint(cp.manifest_entry());
+    drain_on_rotate: bool,
+    last_error: Mutex<Option<String>>,
+    index: Mutex<IndexWriter>,
+    index_local_path: PathBuf,
+    index_remote_path: String,
+    keyring: Option<Keyring>,
+}
+
+impl SpoolSink {
+    pub(crate) fn new(config: &Arc<WatchConfig>, spool_layout: &SpoolLayout) -> Result<Self> {
+        let writer = SessionIndex::writer_id(&local_hostname(), &config.spool_dir);
+        let index_local_path = spool_layout
+            .queue_dir
+            .join(INDEX_DIR)
+            .join(format!("{writer}.json"));
+        let index_remote_path = SessionIndex::remote_path(&config.root_prefix, &writer);
+        Ok(Self {
+            queue: Arc::new(SpoolQueue::new(spool_layout.clone())),
+            uploader: Arc::new(UploadClient::new(config.clone())?),
+            concurrency: config.concurrency.max(1),
+            drain_on_rotate: true,
+            last_error: Mutex::new(None),
+            index: Mutex::new(IndexWriter::load(writer, &config.manifest_state_dir)),
+            index_local_path,
+            index_remote_path,
+            keyring: config.keyring.clone(),
+        })
     }
 
-    let content_type = if closed.content_encoding.is_some() {
-        "application/octet-stream"
-    } else {
-        "application/x-ndjson"
-    };
-    let segment_metadata = SpoolMetadata {
-        remote_path: closed.upload_remote_path.clone(),
-        content_type: Some(content_type.to_string()),
-        content_encoding: closed.content_encoding.clone(),
-        created_at: OffsetDateTime::now_utc(),
-        kind: SpoolItemKind::Segment,
-    };
-    spool_queue
-        .enqueue(&closed.upload_local_path, &segment_metadata)
-        .await?;
+    /// A sink that only queues; the caller decides when to drain.
+    pub(crate) fn deferred(config: &Arc<WatchConfig>, spool_layout: &SpoolLayout) -> Result<Self> {
+        Ok(Self {
+            drain_on_rotate: false,
+            ..Self::new(config, spool_layout)?
+        })
+    }
+
+    pub(crate) async fn drain(&self) -> Result<()> {
+        if let Err(err) = self.queue_index().await {
+            tracing::warn!(error = %err, "failed to queue session index");
+            self.index.lock().mark_dirty();
+        }
+        self.drain_queued().await
+    }
 
-    if let Some(cp) = checkpoint {
-        queue_checkpoint(&cp, spool_queue).await?;
+    /// Upload what is already queued without rewriting the session index first.
+    pub(crate) async fn drain_queued(&self) -> Result<()> {
+        let result = drain_spool(self.queue.clone(), self.uploader.clone(), self.concurrency).await;
+        *self.last_error.lock() = result.as_ref().err().map(|err| err.to_string());
+        result
     }
 
-    queue_manifest(
-        manifest,
-        manifest_store,
-        spool_queue,
-        manifest_upload_path,
-        manifest_remote_path,
-    )
-    .await?;
+    pub(crate) async fn pending(&self) -> Result<usize> {
+        Ok(self.queue.list().await?.len())
+    }
 
-    if let Err(err) = drain_spool(spool_queue.clone(), uploader.clone(), concurrency).await {
-        tracing::warn!(error = %err, "upload failed; data will remain in spool");
+    /// Queued items that belong to the session stored under `prefix`.
+    pub(crate) async fn pending_for(&self, prefix: &str) -> Result<usize> {
+        let prefix = format!("{}/", prefix.trim_end_matches('/'));
+        Ok(self
+            .queue
+            .list()
+            .await?
+            .iter()
+            .filter(|entry| entry.metadata.remote_path.starts_with(&prefix))
+            .count())
+    }
+
+    fn record_manifest(&self, manifest: &Manifest) {
+        self.index.lock().record(manifest);
+    }
+
+    /// Rewrite this uploader's index object if any manifest changed since the last drain.
+    /// Queuing overwrites the previous copy, so only the newest index is ever uploaded.
+    async fn queue_index(&self) -> Result<()> {
+        let Some(index) = self.index.lock().take_dirty() else {
+            return Ok(());
+        };
+        if let Some(parent) = self.index_local_path.parent() {
+            ensure_dir(parent)?;
+        }
+        let mut bytes = index.to_bytes()?;
+        if let Some(keys) = &self.keyring {
+            bytes = keys.seal_json(&self.index_remote_path, &bytes)?;
+        }
+        tokio::fs::write(&self.index_local_path, bytes).await?;
+        let metadata = SpoolMetadata {
+            remote_path: self.index_remote_path.clone(),
+            content_type: Some("application/json".to_string()),
+            content_encoding: None,
+            created_at: OffsetDateTime::now_utc(),
+            kind: SpoolItemKind::Index,
+        };
+        self.queue.enqueue(&self.index_local_path, &metadata).await
+    }
+
+    /// Error from the most recent drain, cleared once a drain succeeds.
+    pub(crate) fn last_error(&self) -> Option<String> {
+        self.last_error.lock().clone()
     }
-    Ok(())
 }
 
-async fn queue_manifest(
-    manifest: &Manifest,
-    manifest_store: &ManifestStore,
-    spool_queue: &Arc<SpoolQueue>,
-    manifest_upload_path: &Path,
-    manifest_remote_path: &str,
-) -> Result<()> {
-    manifest_store.save(manifest)?;
-    let bytes = manifest.to_bytes()?;
-    tokio::fs::write(manifest_upload_path, &bytes).await?;
-    let manifest_metadata = SpoolMetadata {
-        remote_path: manifest_remote_path.to_string(),
-        content_type: Some("application/json".to_string()),
-        content_encoding: None,
-        created_at: OffsetDateTime::now_utc(),
-        kind: SpoolItemKind::Manifest,
+fn segment_spool_metadata(
+    remote_path: String,
+    content_encoding: Option<String>,
+    kind: SpoolItemKind,
+) -> SpoolMetadata {
+    let content_type = if content_encoding.is_some() || remote_path.ends_with(SEALED_SUFFIX) {
+        "application/octet-stream"
+    } else {
+        "application/x-ndjson"
     };
-    spool_queue
-        .enqueue(manifest_upload_path, &manifest_metadata)
-        .await?;
-    Ok(())
+    SpoolMetadata {
+        remote_path,
+        content_type: Some(content_type.to_string()),
+        content_encoding,
+        created_at: OffsetDateTime::now_utc(),
+        kind,
+    }
 }
 
 async fn queue_checkpoint(
     checkpoint: &PendingCheckpoint,
-    spool_queue: &Arc<SpoolQueue>,
+    spool_queue: &SpoolQueue,
+    keys: Option<&Keyring>,
 ) -> Result<()> {
     if let Some(parent) = checkpoint.file_path.parent() {
         ensure_dir(parent)?;
     }
-    let bytes = checkpoint.file_bytes()?;
+    let mut bytes = checkpoint.file_bytes()?;
+    if let Some(keys) = keys {
+        bytes = keys.seal_json(&checkpoint.remote_path, &bytes)?;
+    }
     tokio::fs::write(&checkpoint.file_path, &bytes).await?;
     let metadata = SpoolMetadata {
         remote_path: checkpoint.remote_path.clone(),
@@ -267,3 +937,239 @@ async fn drain_spool(
     .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Cli, Command};
+    use crate::manifest::{SegmentEntry, SessionStatus};
+    use crate::storage::{LocalInspector, StorageInspector, decode_segment};
+    use clap::Parser;
+    use std::io::Write;
+
+    fn config(root: &Path, extra: &[&str]) -> Arc<WatchConfig> {
+        let file = root.join("session.jsonl");
+        let spool = root.join("spool");
+        let mut args = vec![
+            "agent-uploader",
+            "watch",
+            "--file",
+            file.to_str().unwrap(),
+            "--sid",
+            "s1",
+            "--dry-run",
+            "--ui-disable",
+            "--heartbeat-ms",
+            "0",
+            "--spool-dir",
+            spool.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let cli = Cli::try_parse_from(args).expect("valid args");
+        let Command::Watch(args) = cli.command else {
+            panic!("expected watch command");
+        };
+        Arc::new(WatchConfig::from_args(*args).expect("config"))
+    }
+
+    fn append(root: &Path, line: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(root.join("session.jsonl"))
+            .unwrap();
+        writeln!(file, "{line}").unwrap();
+    }
+
+    async fn open(config: &Arc<WatchConfig>) -> (SessionWatcher, SpoolSink) {
+        let layout = SpoolLayout::from_config(config);
+        layout.ensure().unwrap();
+        let sink = SpoolSink::new(config, &layout).unwrap();
+        let watcher = SessionWatcher::open(config.clone(), &layout, 0, None)
+            .await
+            .unwrap();
+        (watcher, sink)
+    }
+
+    #[tokio::test]
+    async fn end_event_completes_and_new_lines_reopen() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path(), &["--end-event", "session_end"]);
+        append(
+            root.path(),
+            r#"{"timestamp":"2025-10-04T15:16:09Z","type":"event_msg"}"#,
+        );
+        let (mut watcher, sink) = open(&config).await;
+        watcher.poll(&sink).await.unwrap();
+        assert_eq!(watcher.end_reason(), None);
+
+        append(
+            root.path(),
+            r#"{"timestamp":"2025-10-04T15:20:00Z","type":"session_end"}"#,
+        );
+        watcher.poll(&sink).await.unwrap();
+        assert_eq!(watcher.end_reason(), Some("end event"));
+        watcher.complete(&sink).await.unwrap();
+        let manifest = watcher.manifest();
+        assert_eq!(manifest.status, SessionStatus::Complete);
+        assert_eq!(
+            manifest.ended_at.map(OffsetDateTime::unix_timestamp),
+            Some(1_759_591_200)
+        );
+        assert_eq!(manifest.total_lines(), 2);
+
+        append(
+            root.path(),
+            r#"{"timestamp":"2025-10-04T15:21:00Z","type":"event_msg"}"#,
+        );
+        watcher.poll(&sink).await.unwrap();
+        assert_eq!(watcher.manifest().status, SessionStatus::Live);
+        assert_eq!(watcher.manifest().ended_at, None);
+    }
+
+    #[tokio::test]
+    async fn resuming_mid_file_keeps_the_head_format() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path(), &[]);
+        append(root.path(), "codex starting");
+        append(
+            root.path(),
+            r#"{"timestamp":"2025-10-04T15:16:09Z","type":"event_msg"}"#,
+        );
+        let (mut watcher, sink) = open(&config).await;
+        watcher.poll(&sink).await.unwrap();
+        assert_eq!(watcher.manifest().format, Some(SessionFormat::Codex));
+        let offset = watcher.manifest().source_offset;
+        drop(watcher);
+
+        // A restart resumes at a line that on its own would look like plain text.
+        append(root.path(), "not json");
+        let layout = SpoolLayout::from_config(&config);
+        let watcher = SessionWatcher::open(config.clone(), &layout, offset, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            watcher.tail_reader.parser().adapter().unwrap().name(),
+            "codex"
+        );
+    }
+
+    #[tokio::test]
+    async fn restarting_a_file_watch_keeps_token_totals() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path(), &[]);
+        let token_count = |total: u64| {
+            format!(
+                r#"{{"timestamp":"2025-10-04T15:16:09Z","type":"event_msg","payload":{{"type":"token_count","info":{{"total_token_usage":{{"input_tokens":{total},"total_tokens":{total}}}}}}}}}"#
+            )
+        };
+        append(root.path(), &token_count(100));
+        let (mut watcher, sink) = open(&config).await;
+        watcher.poll(&sink).await.unwrap();
+        watcher.finalize(&sink).await.unwrap();
+        assert_eq!(watcher.manifest().stats.total_tokens().total_tokens, 100);
+        drop(watcher);
+
+        append(root.path(), &token_count(150));
+        let offset = file_start_offset(&config);
+        assert!(offset > 0);
+        let layout = SpoolLayout::from_config(&config);
+        let mut watcher = SessionWatcher::open(config.clone(), &layout, offset, None)
+            .await
+            .unwrap();
+        watcher.poll(&sink).await.unwrap();
+        watcher.finalize(&sink).await.unwrap();
+        let manifest = watcher.manifest();
+        assert_eq!(manifest.stats.total_tokens().total_tokens, 150);
+        assert_eq!(manifest.total_lines(), 2);
+    }
+
+    #[tokio::test]
+    async fn sealed_segments_open_only_at_their_own_path() {
+        let root = tempfile::tempdir().unwrap();
+        let key = root.path().join("key");
+        std::fs::write(&key, "44".repeat(32)).unwrap();
+        let config = config(
+            root.path(),
+            &[
+                "--encryption-key",
+                key.to_str().unwrap(),
+                "--seg-lines",
+                "1",
+            ],
+        );
+        append(root.path(), r#"{"type":"event_msg","n":1}"#);
+        append(root.path(), r#"{"type":"event_msg","n":2}"#);
+        let layout = SpoolLayout::from_config(&config);
+        layout.ensure().unwrap();
+        let sink = SpoolSink::deferred(&config, &layout).unwrap();
+        let mut watcher = SessionWatcher::open(config.clone(), &layout, 0, None)
+            .await
+            .unwrap();
+        watcher.poll(&sink).await.unwrap();
+        let manifest = watcher.manifest().clone();
+        let key_id = config.keyring.as_ref().unwrap().info().key_id;
+        assert!(
+            manifest
+                .segments
+                .iter()
+                .all(|segment| segment.key_id.as_deref() == Some(key_id.as_str()))
+        );
+
+        let local = LocalInspector::new(config.manifest_state_dir.clone(), layout.clone())
+            .with_keys(config.keyring.clone());
+        let first = &manifest.segments[0];
+        let lines = local
+            .fetch_segment_lines(&config.root_prefix, "s1", &manifest, first)
+            .await
+            .unwrap();
+        assert_eq!(lines[0]["n"], 1);
+
+        // The second segment's object served under the first one's path does not open.
+        let object = |segment: &SegmentEntry| {
+            StorageInspector::segment_object_path(&config.root_prefix, "s1", &segment.path)
+        };
+        let swapped = local
+            .fetch_object_if_exists(&object(&manifest.segments[1]))
+            .await
+            .unwrap()
+            .unwrap();
+        let keys = config.keyring.as_ref();
+        assert!(decode_segment(&object(first), &manifest, first, swapped, keys).is_err());
+
+        // Neither a plaintext object nor one sealed with another key than recorded is
+        // accepted in place of a sealed segment.
+        let mut clear = first.clone();
+        clear.path = clear.path.trim_end_matches(SEALED_SUFFIX).to_string();
+        let plain = b"{\"n\":1}\n".to_vec();
+        let err = decode_segment(&object(&clear), &manifest, &clear, plain, keys).unwrap_err();
+        assert!(err.to_string().contains("stored in clear"), "{err}");
+        let mut rekeyed = first.clone();
+        rekeyed.key_id = Some("0000000000000000".to_string());
+        let sealed = local
+            .fetch_object_if_exists(&object(first))
+            .await
+            .unwrap()
+            .unwrap();
+        let err = decode_segment(&object(first), &manifest, &rekeyed, sealed, keys).unwrap_err();
+        assert!(err.to_string().contains("not 0000000000000000"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn idle_and_exited_process_end_the_session() {
+        let root = tempfile::tempdir().unwrap();
+        append(root.path(), r#"{"type":"event_msg"}"#);
+
+        let idle = config(root.path(), &["--end-idle-ms", "1"]);
+        let (watcher, _sink) = open(&idle).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(watcher.end_reason(), Some("idle timeout"));
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id().to_string();
+        child.wait().unwrap();
+        let exited = config(root.path(), &["--pid", &pid]);
+        let (watcher, _sink) = open(&exited).await;
+        assert_eq!(watcher.end_reason(), Some("process exited"));
+    }
+}